@@ -0,0 +1,270 @@
+use crate::{
+    data::title_rules::{TitleRule, TitleRules},
+    AppError, Context,
+};
+
+use anyhow::anyhow;
+use poise::{serenity_prelude::CreateEmbed, CreateReply};
+
+/// Most title rules a guild can have.
+const MAX_TITLE_RULES: usize = 50;
+
+/// Tools for the guild's admins
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("title_test", "title_rules"),
+    subcommand_required
+)]
+pub async fn admin(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Preview what the title rules make of a title
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "titletest"
+)]
+pub async fn title_test(
+    ctx: Context<'_>,
+    #[description = "A track title, as the source gives it"] title: String,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let clean_title = ctx
+        .data()
+        .title_rules_of(guild_id)
+        .await
+        .clean(&title, false);
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Title rules preview")
+                .field("Before", format!("`{}`", title), false)
+                .field("Title", format!("`{}`", clean_title.title), false)
+                .field(
+                    "Artist",
+                    match clean_title.artist {
+                        Some(artist) => format!("`{}`", artist),
+                        None => "Unknown".to_string(),
+                    },
+                    false,
+                ),
+        ),
+    )
+    .await
+    .map_err(|e| {
+        AppError::from(anyhow!(
+            "commands::admin::title_test: can't send message: {}",
+            e
+        ))
+    })?;
+
+    Ok(())
+}
+
+/// Manage how track titles are cleaned up in this guild
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "titlerules",
+    subcommands("list_rules", "add_rule", "remove_rule", "reset_rules"),
+    subcommand_required
+)]
+pub async fn title_rules(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// List the title rules, in the order they're applied
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "list"
+)]
+pub async fn list_rules(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let own_rules = ctx.data().guild_settings.get(guild_id).await.title_rules;
+    let (rules, whose) = match own_rules {
+        Some(rules) => (rules, "This guild's rules"),
+        None => (ctx.data().title_rules.rules(), "Default rules"),
+    };
+    let description = match rules.is_empty() {
+        true => "No rules, titles are kept as they are.".to_string(),
+        false => rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                let mut line =
+                    format!("{}. `{}` → `{}`", index + 1, rule.pattern, rule.replacement);
+                if let Some(artist) = &rule.artist {
+                    line.push_str(&format!(", artist `{}`", artist));
+                }
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+
+    ctx.send(
+        CreateReply::default().embed(CreateEmbed::default().title(whose).description(description)),
+    )
+    .await
+    .map_err(|e| {
+        AppError::from(anyhow!(
+            "commands::admin::list_rules: can't send message: {}",
+            e
+        ))
+    })?;
+
+    Ok(())
+}
+
+/// Add a title rule, the default ones are copied first
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "add"
+)]
+pub async fn add_rule(
+    ctx: Context<'_>,
+    #[description = "Regex to look for"] pattern: String,
+    #[description = "What to replace it with, nothing by default"] replacement: Option<String>,
+    #[description = "Take the artist out of the title with this, like $artist"] artist: Option<
+        String,
+    >,
+    #[description = "Where to put it, at the end by default"]
+    #[min = 1]
+    position: Option<usize>,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let rule = TitleRule {
+        pattern,
+        replacement: replacement.unwrap_or_default(),
+        artist,
+    };
+    if let Err(e) = TitleRules::compile(std::slice::from_ref(&rule)) {
+        if let Err(e) = ctx.say(format!("That's not a valid regex: {}", e)).await {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    let default_rules = ctx.data().title_rules.rules();
+    let mut added_at = None;
+    ctx.data()
+        .guild_settings
+        .update(guild_id, |settings| {
+            let rules = settings.title_rules.get_or_insert(default_rules);
+            if rules.len() < MAX_TITLE_RULES {
+                let index =
+                    position.map_or(rules.len(), |position| (position - 1).min(rules.len()));
+                rules.insert(index, rule);
+                added_at = Some(index + 1);
+            }
+        })
+        .await;
+
+    let content = match added_at {
+        Some(position) => format!("Added rule `{}`", position),
+        None => format!(
+            "There are already `{}` rules, remove some first!",
+            MAX_TITLE_RULES
+        ),
+    };
+    if let Err(e) = ctx.say(content).await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Remove a title rule, the default ones are copied first
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "remove"
+)]
+pub async fn remove_rule(
+    ctx: Context<'_>,
+    #[description = "Its number in /admin titlerules list"]
+    #[min = 1]
+    position: usize,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let default_rules = ctx.data().title_rules.rules();
+    let mut removed = None;
+    ctx.data()
+        .guild_settings
+        .update(guild_id, |settings| {
+            let rules = settings.title_rules.get_or_insert(default_rules);
+            if (1..=rules.len()).contains(&position) {
+                removed = Some(rules.remove(position - 1));
+            }
+        })
+        .await;
+
+    let content = match removed {
+        Some(rule) => format!("Removed `{}`", rule.pattern),
+        None => "There's no rule with that number.".to_string(),
+    };
+    if let Err(e) = ctx.say(content).await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Go back to the default title rules
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "reset"
+)]
+pub async fn reset_rules(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    ctx.data()
+        .guild_settings
+        .update(guild_id, |settings| settings.title_rules = None)
+        .await;
+
+    if let Err(e) = ctx.say("Back to the default title rules").await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
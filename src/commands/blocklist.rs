@@ -0,0 +1,177 @@
+use crate::{
+    data::blocklist::{compile_title_pattern, BlockKind},
+    AppError, Context,
+};
+
+use anyhow::anyhow;
+use poise::{serenity_prelude::CreateEmbed, ChoiceParameter, CreateReply};
+
+/// Most entries of each kind a guild's blocklist can have.
+const MAX_ENTRIES: usize = 100;
+
+/// Keep tracks out of the queue by source, uploader or title
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("list", "add", "remove"),
+    subcommand_required
+)]
+pub async fn blocklist(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Show what's blocked in this guild
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn list(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let blocklist = ctx.data().guild_settings.get(guild_id).await.blocklist;
+    if blocklist.is_empty() {
+        if let Err(e) = ctx.say("Nothing is blocked.").await {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    let mut embed = CreateEmbed::default().title("Blocklist");
+    for kind in [BlockKind::Url, BlockKind::Uploader, BlockKind::Title] {
+        let entries = blocklist.entries(kind);
+        if entries.is_empty() {
+            continue;
+        }
+        let mut value = String::new();
+        for entry in entries {
+            let line = format!("`{}`\n", entry.replace('`', "'"));
+            // embed fields can't be longer than that
+            if value.len() + line.len() > 1000 {
+                value.push_str("...");
+                break;
+            }
+            value.push_str(&line);
+        }
+        embed = embed.field(kind.name(), value, false);
+    }
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await
+        .map_err(|e| {
+            AppError::from(anyhow!(
+                "commands::blocklist::list: can't send message: {}",
+                e
+            ))
+        })?;
+
+    Ok(())
+}
+
+/// Block a source URL, an uploader or titles matching a regex
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "What to match against"] kind: BlockKind,
+    #[description = "A URL or part of one, an uploader name, or a regex"] value: String,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let value = value.trim().to_string();
+    if value.is_empty() {
+        if let Err(e) = ctx.say("That's empty!").await {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+    if kind == BlockKind::Title {
+        if let Err(e) = compile_title_pattern(&value) {
+            if let Err(e) = ctx.say(format!("That's not a valid regex: {}", e)).await {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    }
+
+    let mut result = Ok(());
+    ctx.data()
+        .guild_settings
+        .update(guild_id, |settings| {
+            let entries = settings.blocklist.entries_mut(kind);
+            result = if entries.contains(&value) {
+                Err("That's already blocked!".to_string())
+            } else if entries.len() >= MAX_ENTRIES {
+                Err(format!(
+                    "There are already `{}` entries of that kind, remove some first!",
+                    MAX_ENTRIES
+                ))
+            } else {
+                entries.push(value.clone());
+                Ok(())
+            };
+        })
+        .await;
+
+    let content = match result {
+        Ok(_) => format!("Blocked {} `{}`", kind.name().to_lowercase(), value),
+        Err(reason) => reason,
+    };
+    if let Err(e) = ctx.say(content).await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Unblock something
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "What it was matched against"] kind: BlockKind,
+    #[description = "The entry, as in /blocklist list"] value: String,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let value = value.trim().to_string();
+    let mut removed = false;
+    ctx.data()
+        .guild_settings
+        .update(guild_id, |settings| {
+            let entries = settings.blocklist.entries_mut(kind);
+            let count = entries.len();
+            entries.retain(|entry| *entry != value);
+            removed = entries.len() < count;
+        })
+        .await;
+
+    let content = match removed {
+        true => format!("Unblocked {} `{}`", kind.name().to_lowercase(), value),
+        false => "That's not in the blocklist!".to_string(),
+    };
+    if let Err(e) = ctx.say(content).await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
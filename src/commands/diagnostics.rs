@@ -0,0 +1,53 @@
+use crate::{AppError, Context};
+
+use anyhow::anyhow;
+use poise::{serenity_prelude::CreateEmbed, CreateReply};
+
+/// Format a size in bytes into a human readable string.
+fn pretty_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Show the App's internal stats
+#[poise::command(prefix_command, slash_command)]
+pub async fn diagnostics(ctx: Context<'_>) -> Result<(), AppError> {
+    let cache_stats = ctx.data().player_data.track_cache.stats().await;
+    let hit_rate = match cache_stats.hits + cache_stats.misses {
+        0 => "N/A".to_string(),
+        lookups => format!("{:.1}%", cache_stats.hits as f64 * 100.0 / lookups as f64),
+    };
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::new()
+                .title("Diagnostics")
+                .color(0x0a5c36)
+                .fields(vec![
+                    (
+                        "Track cache",
+                        format!(
+                            "{} tracks, {} / {}",
+                            cache_stats.entries,
+                            pretty_size(cache_stats.total_size),
+                            pretty_size(cache_stats.max_size)
+                        ),
+                        false,
+                    ),
+                    ("Cache hits", cache_stats.hits.to_string(), true),
+                    ("Cache misses", cache_stats.misses.to_string(), true),
+                    ("Hit rate", hit_rate, true),
+                ]),
+        ),
+    )
+    .await
+    .map_err(|e| AppError::from(anyhow!("can't send message: {}", e)))?;
+
+    Ok(())
+}
@@ -1,7 +1,11 @@
+pub mod admin;
+pub mod blocklist;
 pub mod dcl;
+pub mod diagnostics;
 pub mod help;
 pub mod kqt;
 pub mod ping;
 pub mod player;
 pub mod qt;
+pub mod settings;
 pub mod text_reaction;
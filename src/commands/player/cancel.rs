@@ -0,0 +1,31 @@
+use crate::{AppError, Context};
+
+/// Stop your running playlist imports, tracks already added stay queued
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn cancel(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => {
+            if let Err(e) = ctx.say("This command must be invoke in a guild!").await {
+                tracing::warn!("can't send message 'guild command only': {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    let cancelled = match ctx.data().player_data.guild_player(guild_id).await {
+        Some(guild_player) => guild_player.cancel_imports_of(ctx.author().id),
+        None => 0,
+    };
+
+    let content = match cancelled {
+        0 => "You have no running import!".to_string(),
+        1 => "Cancelled `1` import.".to_string(),
+        count => format!("Cancelled `{}` imports.", count),
+    };
+    if let Err(e) = ctx.say(content).await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use super::voice::voice_channel_of;
+use crate::{data::player_data::TrackInfo, AppError, Context};
+
+use anyhow::anyhow;
+use poise::serenity_prelude::{Cache, GuildId, UserId};
+
+/// Only people listening along can control the player. Anyone can while
+/// the bot isn't in a voice channel.
+pub fn ensure_can_control(cache: &Cache, guild_id: GuildId, user_id: UserId) -> Result<(), String> {
+    let bot_channel_id = voice_channel_of(cache, guild_id, cache.current_user().id);
+    match bot_channel_id {
+        Some(bot_channel_id)
+            if voice_channel_of(cache, guild_id, user_id) != Some(bot_channel_id) =>
+        {
+            Err(format!(
+                "You must be in <#{}> to control the player!",
+                bot_channel_id
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Anyone can skip or remove the tracks they asked for, the others follow
+/// [`ensure_can_control`].
+pub fn ensure_can_control_track(
+    cache: &Cache,
+    guild_id: GuildId,
+    user_id: UserId,
+    track_info: &TrackInfo,
+) -> Result<(), String> {
+    match track_info
+        .requester
+        .as_ref()
+        .is_some_and(|requester| requester.id == user_id)
+    {
+        true => Ok(()),
+        false => ensure_can_control(cache, guild_id, user_id),
+    }
+}
+
+/// Command check version of [`ensure_can_control`].
+pub async fn can_control(ctx: Context<'_>) -> Result<bool, AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(true),
+    };
+    ensure_can_control(ctx.cache(), guild_id, ctx.author().id)
+        .map(|_| true)
+        .map_err(|e| AppError::from(anyhow!(e)))
+}
@@ -0,0 +1,56 @@
+use crate::{AppError, Context};
+
+use anyhow::anyhow;
+use poise::{serenity_prelude::CreateEmbed, CreateReply};
+
+/// How many entries /history shows.
+const SHOWN_ENTRIES: usize = 20;
+
+/// Show what played recently, radio songs included
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn history(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let history = match ctx.data().player_data.guild_player(guild_id).await {
+        Some(guild_player) => guild_player.history().await,
+        None => Vec::new(),
+    };
+    if history.is_empty() {
+        if let Err(e) = ctx.say("Nothing played yet!").await {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::default().title("History").description(
+                history
+                    .iter()
+                    .take(SHOWN_ENTRIES)
+                    .map(|entry| {
+                        format!(
+                            "<t:{}:R> [{}]({})",
+                            entry.played_at,
+                            entry.title.replace(['[', ']'], ""),
+                            entry.url
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        ),
+    )
+    .await
+    .map_err(|e| {
+        AppError::from(anyhow!(
+            "commands::player::history: can't send message: {}",
+            e
+        ))
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use super::voice::{self, voice_channel_of};
+use crate::{AppError, Context};
+
+use poise::serenity_prelude::{ChannelId, ChannelType, GuildChannel, GuildId};
+
+/// Join or move to a voice channel, the queue and the playing track go along.
+pub async fn join_channel(
+    ctx: Context<'_>,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<(), AppError> {
+    if voice_channel_of(ctx.cache(), guild_id, ctx.cache().current_user().id) == Some(channel_id) {
+        if let Err(e) = ctx.say(format!("Already in <#{}>!", channel_id)).await {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    let member = match ctx.author_member().await {
+        Some(member) => member,
+        None => {
+            if let Err(e) = ctx.say("Can't find who you are, try again later.").await {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+    if let Err(reason) = voice::ensure_can_move(ctx.cache(), guild_id, &member)
+        .and_then(|_| voice::ensure_can_connect(ctx.cache(), guild_id, channel_id, &member))
+    {
+        if let Err(e) = ctx.say(reason).await {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    let content = match voice::join(
+        ctx.serenity_context(),
+        &ctx.data().player_data,
+        guild_id,
+        channel_id,
+    )
+    .await
+    {
+        Ok(_) => format!("Joined <#{}>", channel_id),
+        Err(e) => e,
+    };
+    if let Err(e) = ctx.say(content).await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Join a voice channel, yours if none is given
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn join(
+    ctx: Context<'_>,
+    #[description = "Voice or stage channel to join"]
+    #[channel_types("Voice", "Stage")]
+    channel: Option<GuildChannel>,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => {
+            if let Err(e) = ctx.say("This command must be invoke in a guild!").await {
+                tracing::warn!("can't send message 'guild command only': {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    let channel_id = match channel {
+        Some(channel) if !matches!(channel.kind, ChannelType::Voice | ChannelType::Stage) => {
+            if let Err(e) = ctx.say("That's not a voice channel!").await {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+        Some(channel) => channel.id,
+        None => match voice_channel_of(ctx.cache(), guild_id, ctx.author().id) {
+            Some(channel_id) => channel_id,
+            None => {
+                if let Err(e) = ctx.say("You're not in a voice channel!").await {
+                    tracing::warn!("can't send message: {}", e);
+                }
+                return Ok(());
+            }
+        },
+    };
+
+    join_channel(ctx, guild_id, channel_id).await
+}
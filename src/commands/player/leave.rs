@@ -0,0 +1,64 @@
+use crate::{AppError, Context};
+
+use anyhow::anyhow;
+
+/// Leave the voice channel, the queue is kept for when the bot joins again
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    check = "super::checks::can_control"
+)]
+pub async fn leave(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => {
+            if let Err(e) = ctx.say("This command must be invoke in a guild!").await {
+                tracing::warn!("can't send message 'guild command only': {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    let songbird_manager = match songbird::get(ctx.serenity_context()).await {
+        Some(songbird_manager) => songbird_manager,
+        None => {
+            return Err(AppError::from(anyhow!(
+                "commands::player::leave: songbird not loaded"
+            )));
+        }
+    };
+
+    let connected = match songbird_manager.get(guild_id) {
+        Some(call) => call.lock().await.current_channel().is_some(),
+        None => false,
+    };
+    if !connected {
+        if let Err(e) = ctx.say("Not in a voice channel.").await {
+            tracing::warn!("can't send message 'not in a voice channel': {}", e);
+        }
+        return Ok(());
+    }
+
+    // the call stays around with its queue, only the connection goes away
+    if let Some(guild_player) = ctx.data().player_data.guild_player(guild_id).await {
+        guild_player.cancel_sleep_timer().await;
+        guild_player.pause_for_leave().await;
+        guild_player.start_leaving().await;
+    }
+    songbird_manager.leave(guild_id).await.map_err(|e| {
+        AppError::from(anyhow!(
+            "commands::player::leave: can't leave voice channel: {}",
+            e
+        ))
+    })?;
+
+    if let Err(e) = ctx
+        .say("👋 Left, the queue is kept until `/join` or `/play`.")
+        .await
+    {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
@@ -1,14 +1,41 @@
+mod cancel;
+mod checks;
+mod history;
+mod join;
+mod leave;
+mod now_playing;
 mod nuke;
 mod pause;
 mod play;
 mod queue;
+mod radio;
+mod reconnect;
 mod restart;
+mod schedule;
+mod sfx;
 mod skip;
+mod sleep;
+mod stats;
+mod summon;
+mod time;
 mod track_event_handler;
+mod voice;
 
+pub use cancel::cancel;
+pub use history::history;
+pub use join::join;
+pub use leave::leave;
+pub use now_playing::handle_press as handle_panel_press;
 pub use nuke::nuke;
 pub use pause::pause;
-pub use play::play;
+pub use play::{play, start_breakage_reports};
 pub use queue::queue;
+pub use radio::{radio, stations};
+pub use reconnect::handle_voice_state_update;
 pub use restart::restart;
+pub use schedule::{schedule, start_scheduler};
+pub use sfx::sfx;
 pub use skip::skip;
+pub use sleep::sleep;
+pub use stats::stats;
+pub use summon::summon;
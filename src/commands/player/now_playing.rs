@@ -0,0 +1,432 @@
+use crate::data::{
+    guild_settings::{GuildSettings, NowPlayingMode},
+    player_data::{GuildPlayer, LoopMode, NowPlayingPanel, TrackInfo, MAX_VOLUME_PERCENT},
+    Data,
+};
+
+use poise::serenity_prelude::{
+    ButtonStyle, ChannelId, ComponentInteraction, Context as SerenityContext, CreateActionRow,
+    CreateButton, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter, CreateInteractionResponse,
+    CreateInteractionResponseMessage, CreateMessage, EditMessage, GetMessages, Http,
+};
+use songbird::tracks::PlayMode;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Custom IDs of the panel's buttons start with this, so that the global
+/// interaction handler can tell them apart from other components.
+const CUSTOM_ID_PREFIX: &str = "np-panel-";
+
+const VOLUME_STEP_PERCENT: u32 = 10;
+
+#[derive(Debug, Clone, Copy)]
+enum PanelAction {
+    PauseResume,
+    Skip,
+    Stop,
+    Loop,
+    Shuffle,
+    VolumeDown,
+    VolumeUp,
+}
+
+impl PanelAction {
+    const ALL: [PanelAction; 7] = [
+        PanelAction::PauseResume,
+        PanelAction::Skip,
+        PanelAction::Stop,
+        PanelAction::Loop,
+        PanelAction::Shuffle,
+        PanelAction::VolumeDown,
+        PanelAction::VolumeUp,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            PanelAction::PauseResume => "pause",
+            PanelAction::Skip => "skip",
+            PanelAction::Stop => "stop",
+            PanelAction::Loop => "loop",
+            PanelAction::Shuffle => "shuffle",
+            PanelAction::VolumeDown => "vol-down",
+            PanelAction::VolumeUp => "vol-up",
+        }
+    }
+
+    fn custom_id(self) -> String {
+        format!("{}{}", CUSTOM_ID_PREFIX, self.name())
+    }
+
+    fn from_custom_id(custom_id: &str) -> Option<Self> {
+        let name = custom_id.strip_prefix(CUSTOM_ID_PREFIX)?;
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// What the panel shows besides the track itself.
+struct PanelState {
+    paused: bool,
+    loop_mode: LoopMode,
+    volume_percent: u32,
+}
+
+impl PanelState {
+    async fn of(guild_player: &GuildPlayer, paused: bool) -> Self {
+        Self {
+            paused,
+            loop_mode: guild_player.loop_mode().await,
+            volume_percent: guild_player.volume_percent().await,
+        }
+    }
+}
+
+fn loop_mode_label(loop_mode: LoopMode) -> &'static str {
+    match loop_mode {
+        LoopMode::Off => "Off",
+        LoopMode::Track => "Track",
+        LoopMode::Queue => "Queue",
+    }
+}
+
+fn panel_embed(track_info: &TrackInfo, state: &PanelState) -> CreateEmbed {
+    let mut embed = CreateEmbed::default()
+        .author(CreateEmbedAuthor::new(match state.paused {
+            true => "Paused",
+            false => "Now playing",
+        }))
+        .title(track_info.get_title())
+        .description(match track_info.stream_title.get() {
+            Some(song) => format!("🎵 {}\n{}", song, track_info.get_pretty_description()),
+            None => track_info.get_pretty_description(),
+        })
+        .url(&track_info.url)
+        .footer(CreateEmbedFooter::new(format!(
+            "{}🔁 Loop: {} • 🔊 Volume: {}%",
+            match track_info.requester_name() {
+                Some(name) => format!("👤 {} • ", name),
+                None => String::new(),
+            },
+            loop_mode_label(state.loop_mode),
+            state.volume_percent
+        )));
+    if let Some(thumbnail) = track_info.thumbnail.clone() {
+        embed = embed.thumbnail(thumbnail);
+    }
+    embed
+}
+
+fn panel_buttons(state: &PanelState) -> Vec<CreateActionRow> {
+    let button = |action: PanelAction, label: String| {
+        CreateButton::new(action.custom_id())
+            .label(label)
+            .style(ButtonStyle::Secondary)
+    };
+    vec![
+        CreateActionRow::Buttons(vec![
+            button(
+                PanelAction::PauseResume,
+                match state.paused {
+                    true => "▶️ Resume".to_string(),
+                    false => "⏸️ Pause".to_string(),
+                },
+            )
+            .style(ButtonStyle::Primary),
+            button(PanelAction::Skip, "⏭️ Skip".to_string()),
+            button(PanelAction::Stop, "⏹️ Stop".to_string()).style(ButtonStyle::Danger),
+        ]),
+        CreateActionRow::Buttons(vec![
+            button(
+                PanelAction::Loop,
+                format!("🔁 {}", loop_mode_label(state.loop_mode)),
+            ),
+            button(PanelAction::Shuffle, "🔀 Shuffle".to_string()),
+            button(PanelAction::VolumeDown, "🔉 -".to_string()).disabled(state.volume_percent == 0),
+            button(PanelAction::VolumeUp, "🔊 +".to_string())
+                .disabled(state.volume_percent >= MAX_VOLUME_PERCENT),
+        ]),
+    ]
+}
+
+/// Remove the buttons of a panel, its track isn't playing anymore.
+async fn strip_panel(http: &Http, panel: NowPlayingPanel) {
+    if let Err(e) = panel
+        .channel_id
+        .edit_message(
+            http,
+            panel.message_id,
+            EditMessage::new().components(vec![]),
+        )
+        .await
+    {
+        warn!("can't remove the buttons of the 'now playing' panel: {}", e);
+    }
+}
+
+/// Whether a message is the latest one of its channel.
+async fn is_latest_message(http: &Http, panel: NowPlayingPanel) -> bool {
+    match panel
+        .channel_id
+        .messages(http, GetMessages::new().limit(1))
+        .await
+    {
+        Ok(messages) => messages.first().map(|message| message.id) == Some(panel.message_id),
+        Err(e) => {
+            warn!("can't get the latest message of the channel: {}", e);
+            false
+        }
+    }
+}
+
+/// Post the panel of a track that just started, following the guild's
+/// [`NowPlayingMode`].
+pub async fn send_panel(
+    http: &Http,
+    guild_player: &GuildPlayer,
+    guild_settings: &GuildSettings,
+    channel_id: ChannelId,
+    track_id: Uuid,
+    track_info: &TrackInfo,
+) {
+    let state = PanelState::of(guild_player, false).await;
+    let previous_panel = guild_player.panel().await;
+
+    // keep using the single message while nothing was posted after it
+    if guild_settings.now_playing_mode == NowPlayingMode::Single {
+        if let Some(previous_panel) =
+            previous_panel.filter(|previous_panel| previous_panel.channel_id == channel_id)
+        {
+            if is_latest_message(http, previous_panel).await {
+                match channel_id
+                    .edit_message(
+                        http,
+                        previous_panel.message_id,
+                        EditMessage::new()
+                            .embed(panel_embed(track_info, &state))
+                            .components(panel_buttons(&state)),
+                    )
+                    .await
+                {
+                    Ok(_) => {
+                        guild_player
+                            .replace_panel(Some(NowPlayingPanel {
+                                track_id,
+                                ..previous_panel
+                            }))
+                            .await;
+                        return;
+                    }
+                    Err(e) => warn!("can't edit the 'now playing' panel, re-posting it: {}", e),
+                }
+            }
+        }
+    }
+
+    let message = match channel_id
+        .send_message(
+            http,
+            CreateMessage::default()
+                .embed(panel_embed(track_info, &state))
+                .components(panel_buttons(&state)),
+        )
+        .await
+    {
+        Ok(message) => message,
+        Err(e) => {
+            warn!("can't send message 'now playing': {}", e);
+            return;
+        }
+    };
+
+    let previous_panel = guild_player
+        .replace_panel(Some(NowPlayingPanel {
+            track_id,
+            channel_id,
+            message_id: message.id,
+        }))
+        .await;
+    if let Some(previous_panel) = previous_panel {
+        match guild_settings.now_playing_mode {
+            NowPlayingMode::PerTrack => strip_panel(http, previous_panel).await,
+            NowPlayingMode::Single => {
+                if let Err(e) = previous_panel
+                    .channel_id
+                    .delete_message(http, previous_panel.message_id)
+                    .await
+                {
+                    warn!("can't delete the previous 'now playing' panel: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Update the panel of the playing track after its state changed.
+pub async fn refresh_panel(http: &Http, guild_player: &GuildPlayer) {
+    let (panel, (handle, track_info)) =
+        match (guild_player.panel().await, guild_player.current().await) {
+            (Some(panel), Some(current)) => (panel, current),
+            _ => return,
+        };
+    if panel.track_id != handle.uuid() {
+        return;
+    }
+    let paused = match handle.get_info().await {
+        Ok(info) => info.playing == PlayMode::Pause,
+        Err(_) => return,
+    };
+
+    let state = PanelState::of(guild_player, paused).await;
+    if let Err(e) = panel
+        .channel_id
+        .edit_message(
+            http,
+            panel.message_id,
+            EditMessage::new()
+                .embed(panel_embed(&track_info, &state))
+                .components(panel_buttons(&state)),
+        )
+        .await
+    {
+        warn!("can't edit the 'now playing' panel: {}", e);
+    }
+}
+
+/// Disable the current panel, once nothing is playing anymore.
+pub async fn close_panel(http: &Http, guild_player: &GuildPlayer) {
+    if let Some(panel) = guild_player.replace_panel(None).await {
+        strip_panel(http, panel).await;
+    }
+}
+
+async fn respond_ephemeral(
+    ctx: &SerenityContext,
+    interaction: &ComponentInteraction,
+    content: &str,
+) {
+    if let Err(e) = interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await
+    {
+        warn!("can't respond to panel button: {}", e);
+    }
+}
+
+/// Handle a press on any panel's button, other components are ignored.
+pub async fn handle_press(ctx: &SerenityContext, data: &Data, interaction: &ComponentInteraction) {
+    let action = match PanelAction::from_custom_id(&interaction.data.custom_id) {
+        Some(action) => action,
+        None => return,
+    };
+    let guild_id = match interaction.guild_id {
+        Some(guild_id) => guild_id,
+        None => return,
+    };
+
+    let guild_player = match data.player_data.guild_player(guild_id).await {
+        Some(guild_player) => guild_player,
+        None => {
+            respond_ephemeral(ctx, interaction, "Nothing is playing.").await;
+            return;
+        }
+    };
+    let panel = guild_player
+        .panel()
+        .await
+        .filter(|panel| panel.message_id == interaction.message.id);
+    let (handle, track_info) = match (panel, guild_player.current().await) {
+        // a single panel outlives its track until the next one starts
+        (Some(panel), Some(current)) if panel.track_id == current.0.uuid() => current,
+        _ => {
+            respond_ephemeral(ctx, interaction, "This track isn't playing anymore.").await;
+            return;
+        }
+    };
+    let mut paused = match handle.get_info().await {
+        Ok(info) => info.playing == PlayMode::Pause,
+        Err(_) => {
+            respond_ephemeral(ctx, interaction, "Nothing is playing.").await;
+            return;
+        }
+    };
+
+    match action {
+        PanelAction::PauseResume => {
+            let result = match paused {
+                true => handle.play(),
+                false => handle.pause(),
+            };
+            match result {
+                Ok(_) => paused = !paused,
+                Err(e) => warn!("can't change playing state: {}", e),
+            }
+        }
+        PanelAction::Skip | PanelAction::Stop => {
+            // this panel's track is done, the next one gets its own panel,
+            // or takes this one over
+            let now_playing_mode = data.guild_settings.get(guild_id).await.now_playing_mode;
+            if now_playing_mode == NowPlayingMode::PerTrack {
+                guild_player.replace_panel(None).await;
+            }
+            match action {
+                PanelAction::Skip => {
+                    if let Err(e) = guild_player.skip() {
+                        warn!("can't skip track: {}", e);
+                    }
+                }
+                _ => guild_player.stop().await,
+            }
+
+            if let Err(e) = interaction
+                .create_response(
+                    ctx,
+                    CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new().components(vec![]),
+                    ),
+                )
+                .await
+            {
+                warn!("can't respond to panel button: {}", e);
+            }
+            return;
+        }
+        PanelAction::Loop => {
+            let loop_mode = guild_player.loop_mode().await.next();
+            guild_player.set_loop_mode(loop_mode).await;
+        }
+        PanelAction::Shuffle => guild_player.shuffle().await,
+        PanelAction::VolumeDown => {
+            let volume_percent = guild_player.volume_percent().await;
+            guild_player
+                .set_volume_percent(volume_percent.saturating_sub(VOLUME_STEP_PERCENT))
+                .await;
+        }
+        PanelAction::VolumeUp => {
+            let volume_percent = guild_player.volume_percent().await;
+            guild_player
+                .set_volume_percent(volume_percent + VOLUME_STEP_PERCENT)
+                .await;
+        }
+    }
+
+    let state = PanelState::of(&guild_player, paused).await;
+    if let Err(e) = interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .embed(panel_embed(&track_info, &state))
+                    .components(panel_buttons(&state)),
+            ),
+        )
+        .await
+    {
+        warn!("can't respond to panel button: {}", e);
+    }
+}
@@ -21,8 +21,10 @@ pub async fn nuke(ctx: Context<'_>) -> Result<(), AppError> {
         }
     };
 
-    // send nuke signal to any running /play command
-    let _ = ctx.data().player_data.nuke_signal.send(guild_id);
+    // stop any running /play command and track resolution, forget the guild's player
+    if let Some(guild_player) = ctx.data().player_data.remove_guild_player(guild_id).await {
+        super::now_playing::close_panel(ctx.http(), &guild_player).await;
+    }
 
     let songbird_manager = match songbird::get(ctx.serenity_context()).await {
         Some(songbird_manager) => songbird_manager,
@@ -50,39 +52,7 @@ pub async fn nuke(ctx: Context<'_>) -> Result<(), AppError> {
     }
 
     // clear global event handlers
-    {
-        let mut call_global_event_handler_added = ctx
-            .data()
-            .player_data
-            .call_global_event_handler_added
-            .lock()
-            .await;
-        if call_global_event_handler_added.contains(&guild_id) {
-            call.lock().await.remove_all_global_events();
-            call_global_event_handler_added.remove(&guild_id);
-        }
-    }
-
-    // clear guild_2_tracks
-    ctx.data()
-        .player_data
-        .guild_2_tracks
-        .lock()
-        .await
-        .remove(&guild_id);
-
-    // clear track_2_guild
-    ctx.data()
-        .player_data
-        .track_2_guild
-        .lock()
-        .await
-        .retain(|_, guild_id_in_map| guild_id_in_map != &guild_id);
-
-    // clear temp dir
-    if let Err(e) = std::fs::remove_dir_all(format!("/tmp/taxer/{}", guild_id)) {
-        tracing::warn!("can't remove temp dir: {}", e);
-    }
+    call.lock().await.remove_all_global_events();
 
     if let Err(e) = ctx.say("💥 Nuked!").await {
         tracing::warn!("can't send message 'nuked': {}", e);
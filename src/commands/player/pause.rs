@@ -59,6 +59,10 @@ pub async fn pause(ctx: Context<'_>) -> Result<(), AppError> {
             ))
         })?;
 
+        if let Some(guild_player) = ctx.data().player_data.guild_player(guild_id).await {
+            super::now_playing::refresh_panel(ctx.http(), &guild_player).await;
+        }
+
         if let Err(e) = ctx
             .say(match was_playing {
                 true => "⏸️ Paused",
@@ -1,16 +1,376 @@
-use crate::{data::player_data::TrackInfo, AppError, Context};
+use crate::{
+    data::{
+        blocklist::BlocklistMatcher,
+        guild_settings::{DuplicatePolicy, GuildSettings, GuildSettingsStore},
+        player_data::{
+            GuildPlayer, PlayerData, Requester, ResolverKind, SeenTracks, TrackInfo,
+            DOWNLOAD_FORMAT,
+        },
+        stats::StatsStore,
+        title_rules::TitleRules,
+        Data,
+    },
+    AppError, Context,
+};
 
-use std::{collections::VecDeque, io::BufRead, process::Command};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::anyhow;
 use poise::{
-    serenity_prelude::{CreateEmbed, CreateMessage},
+    futures_util::StreamExt,
+    serenity_prelude::{
+        ButtonStyle, ChannelId, ComponentInteractionCollector, Context as SerenityContext,
+        CreateActionRow, CreateButton, CreateEmbed, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateMessage, GuildId, UserId,
+    },
     CreateReply, ReplyHandle,
 };
-use songbird::{input::HttpRequest, tracks::Track};
-use tracing::error;
+use songbird::Call;
+use tokio::{
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
+/// How many blocked tracks are listed to whoever added them.
+const MAX_BLOCKED_SHOWN: usize = 10;
+/// How long the maintainer is left alone after being told `yt-dlp` is broken.
+const BREAKAGE_REPORT_COOLDOWN: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Tell the maintainer in DMs whenever `yt-dlp` can't read a site anymore,
+/// whether it was adding, preparing or downloading a track.
+pub fn start_breakage_reports(ctx: &SerenityContext, data: &Data) {
+    let http = ctx.http.clone();
+    let mut breakages = data.player_data.yt_dlp.breakages();
+    let maintainer = match data.config.bot_maintainer_uid.parse::<u64>() {
+        Ok(uid) if uid != 0 => UserId::new(uid),
+        _ => {
+            warn!(
+                "BOT_MAINTAINER_UID {} isn't a user ID, yt-dlp breakages won't be reported",
+                data.config.bot_maintainer_uid
+            );
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        let mut last_report: Option<Instant> = None;
+        while breakages.changed().await.is_ok() {
+            // fits in a message
+            let error = match &*breakages.borrow_and_update() {
+                Some(error) => error.chars().take(1500).collect::<String>(),
+                None => continue,
+            };
+            if last_report
+                .is_some_and(|last_report| last_report.elapsed() < BREAKAGE_REPORT_COOLDOWN)
+            {
+                continue;
+            }
+            last_report = Some(Instant::now());
+
+            let content = format!(
+                "`yt-dlp` can't read some sites anymore, you might want to update it.\n```\n{}\n```",
+                error
+            );
+            if let Err(e) = maintainer
+                .direct_message(&http, CreateMessage::new().content(content))
+                .await
+            {
+                warn!("can't tell the maintainer about yt-dlp: {}", e);
+            }
+        }
+    });
+}
+
+/// Aborts the task when dropped, so that it doesn't outlive the command.
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// The "Cancel" button of an import's progress message.
+fn cancel_button(custom_id: &str) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![CreateButton::new(custom_id)
+        .label("Cancel")
+        .style(ButtonStyle::Danger)])]
+}
+
+/// What the resolving task sends back to the command.
+enum Resolved {
+    Track(Box<TrackInfo>),
+    /// A query couldn't be resolved, the next ones still are.
+    Failed(String),
+    Done,
+}
+
+/// What a [`TrackFilter`] made of a track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Verdict {
+    Accepted,
+    /// On the guild's blocklist, with why.
+    Blocked(String),
+    /// Already in the queue, added anyway.
+    Duplicate,
+    /// Already in the queue, not added.
+    DuplicateSkipped,
+}
+
+/// Cleans resolved tracks up and checks them against the guild's settings
+/// before they join its queue.
+pub struct TrackFilter {
+    title_rules: TitleRules,
+    blocklist: BlocklistMatcher,
+    duplicate_policy: DuplicatePolicy,
+    /// What's in the queue, and what got added since.
+    seen_tracks: SeenTracks,
+}
+
+impl TrackFilter {
+    pub async fn new(
+        guild_player: &GuildPlayer,
+        title_rules: TitleRules,
+        guild_settings: &GuildSettings,
+    ) -> Self {
+        let seen_tracks = match guild_settings.duplicate_policy {
+            DuplicatePolicy::Allow => SeenTracks::default(),
+            _ => SeenTracks::new(
+                guild_player
+                    .tracks()
+                    .await
+                    .iter()
+                    .map(|(_, track_info)| track_info.as_ref()),
+            ),
+        };
+        Self {
+            title_rules,
+            blocklist: guild_settings.blocklist.matcher(),
+            duplicate_policy: guild_settings.duplicate_policy,
+            seen_tracks,
+        }
+    }
+
+    fn check(&mut self, track_info: &mut TrackInfo) -> Verdict {
+        // station names aren't song titles
+        if track_info.resolver != ResolverKind::Radio {
+            track_info.apply_title_rules(&self.title_rules);
+        }
+        if let Some(reason) = self.blocklist.reason(track_info) {
+            return Verdict::Blocked(reason);
+        }
+
+        let verdict = match self.duplicate_policy {
+            DuplicatePolicy::Allow => return Verdict::Accepted,
+            _ if !self.seen_tracks.contains(track_info) => Verdict::Accepted,
+            DuplicatePolicy::Reject => return Verdict::DuplicateSkipped,
+            _ => Verdict::Duplicate,
+        };
+        self.seen_tracks.insert(track_info);
+        verdict
+    }
+}
+
+/// Resolve a single query, sending its tracks as they come.
+async fn resolve_query(
+    player_data: &PlayerData,
+    query: &str,
+    text_channel_id: ChannelId,
+    requester: &Requester,
+    resolved_tx: &mpsc::Sender<Resolved>,
+) -> Result<(), String> {
+    let mut tracks = player_data
+        .resolvers
+        .for_query(player_data, query)
+        .await
+        .resolve(player_data, query)
+        .await?;
+    while let Some(mut track_info) = tracks.next().await.transpose()? {
+        // assign ID, text channel ID and requester
+        track_info.id = Uuid::new_v4();
+        track_info.text_channel_id = Some(text_channel_id);
+        track_info.requester = Some(requester.clone());
+
+        // the command is gone, no need to go on
+        if let Err(e) = resolved_tx
+            .send(Resolved::Track(Box::new(track_info)))
+            .await
+        {
+            error!("can't send new track to channel: {}", e);
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// Resolve every query in order.
+async fn resolve_queries(
+    player_data: Arc<PlayerData>,
+    queries: Vec<String>,
+    text_channel_id: ChannelId,
+    requester: Requester,
+    resolved_tx: mpsc::Sender<Resolved>,
+) {
+    for query in queries {
+        if let Err(e) = resolve_query(
+            &player_data,
+            &query,
+            text_channel_id,
+            &requester,
+            &resolved_tx,
+        )
+        .await
+        {
+            error!("can't resolve {}: {}", query, e);
+            if resolved_tx.send(Resolved::Failed(e)).await.is_err() {
+                return;
+            }
+        }
+    }
+    if let Err(e) = resolved_tx.send(Resolved::Done).await {
+        error!("can't send Done to channel: {}", e);
+    }
+}
+
+/// Get the player of a guild, adding the global event handlers to its
+/// call when it's new.
+pub async fn guild_player_of(
+    ctx: &SerenityContext,
+    player_data: &Arc<PlayerData>,
+    guild_settings: &Arc<GuildSettingsStore>,
+    stats: &Arc<StatsStore>,
+    guild_id: GuildId,
+    call: &Arc<Mutex<Call>>,
+) -> Arc<GuildPlayer> {
+    let (guild_player, created) = player_data.get_or_create_guild_player(guild_id, call).await;
+    if created {
+        let http = ctx.http.clone();
+        let mut call_ = call.lock().await;
+        call_.add_global_event(
+            songbird::Event::Track(songbird::TrackEvent::Play),
+            super::track_event_handler::PlayEventHandler {
+                player_data: player_data.clone(),
+                guild_player: guild_player.clone(),
+                guild_settings: guild_settings.clone(),
+                stats: stats.clone(),
+                guild_id,
+                http: http.clone(),
+            },
+        );
+        call_.add_global_event(
+            songbird::Event::Track(songbird::TrackEvent::End),
+            super::track_event_handler::EndEventHandler {
+                player_data: player_data.clone(),
+                guild_player: guild_player.clone(),
+                stats: stats.clone(),
+                guild_id,
+                call: call.clone(),
+                http: http.clone(),
+            },
+        );
+        call_.add_global_event(
+            songbird::Event::Track(songbird::TrackEvent::Error),
+            super::track_event_handler::ErrorEventHandler {
+                player_data: player_data.clone(),
+                guild_player: guild_player.clone(),
+                guild_settings: guild_settings.clone(),
+                guild_id,
+                call: call.clone(),
+                http,
+            },
+        );
+        let disconnect_handler = || super::reconnect::DisconnectHandler {
+            ctx: ctx.clone(),
+            player_data: player_data.clone(),
+            guild_player: guild_player.clone(),
+            guild_settings: guild_settings.clone(),
+            guild_id,
+            call: call.clone(),
+        };
+        call_.add_global_event(
+            songbird::Event::Core(songbird::CoreEvent::DriverDisconnect),
+            disconnect_handler(),
+        );
+        call_.add_global_event(
+            songbird::Event::Core(songbird::CoreEvent::DriverReconnect),
+            disconnect_handler(),
+        );
+    }
+    guild_player
+}
+
+/// Resolve queries and add their tracks to the queue with nobody around to
+/// follow along, for scheduled playback. Returns how many tracks were added
+/// and how many queries couldn't be resolved.
+pub async fn enqueue_unattended(
+    player_data: Arc<PlayerData>,
+    guild_player: &GuildPlayer,
+    call: &Mutex<Call>,
+    mut track_filter: TrackFilter,
+    queries: Vec<String>,
+    requester: Requester,
+    text_channel_id: ChannelId,
+) -> (usize, usize) {
+    // /cancel and /nuke stop it like any other import
+    let import = guild_player.start_import(requester.id);
+    let (resolved_tx, mut resolved_rx) = mpsc::channel::<Resolved>(1);
+    let _yt_dlp_thread_guard = AbortOnDrop(tokio::spawn(resolve_queries(
+        player_data.clone(),
+        queries,
+        text_channel_id,
+        requester,
+        resolved_tx,
+    )));
+
+    let (mut track_count, mut failed_count) = (0, 0);
+    loop {
+        tokio::select! {
+            resolved = resolved_rx.recv() => {
+                // the resolver went away without saying it's done
+                let Some(resolved) = resolved else { break };
+                match resolved {
+                    Resolved::Track(mut track_info) => {
+                        match track_filter.check(&mut track_info) {
+                            Verdict::Accepted | Verdict::Duplicate => {}
+                            Verdict::Blocked(reason) => {
+                                info!("not adding {}: {}", track_info.url, reason);
+                                continue;
+                            }
+                            Verdict::DuplicateSkipped => continue,
+                        }
+                        if track_info.resolver == ResolverKind::YtDlp {
+                            track_info.prime_stream_url(&player_data.format_policy);
+                        }
+                        track_count += 1;
+                        guild_player.enqueue(player_data.clone(), call, *track_info).await;
+                    }
+                    Resolved::Failed(_) => failed_count += 1,
+                    Resolved::Done => break,
+                }
+            }
+            _ = import.cancel_token.cancelled() => break,
+        }
+    }
+    (track_count, failed_count)
+}
+
+/// The author of a command, with their name in the guild.
+pub async fn requester_of(ctx: Context<'_>) -> Requester {
+    let name = match ctx.author_member().await {
+        Some(member) => member.display_name().to_string(),
+        None => ctx.author().display_name().to_string(),
+    };
+    Requester {
+        id: ctx.author().id,
+        name,
+    }
+}
+
 /// Play something
 #[poise::command(prefix_command, slash_command, guild_only)]
 pub async fn play(
@@ -22,6 +382,13 @@ pub async fn play(
             "commands::player::play: query is empty, probably due to Discord's side"
         )));
     }
+    enqueue_queries(ctx, vec![query]).await
+}
+
+/// Resolve queries and add their tracks to the queue, joining the caller's
+/// voice channel if needed. Progress is shown in a reply with a button to
+/// cancel.
+pub async fn enqueue_queries(ctx: Context<'_>, queries: Vec<String>) -> Result<(), AppError> {
     let player_data = ctx.data().player_data.clone();
 
     let guild_id = match ctx.guild_id() {
@@ -34,49 +401,31 @@ pub async fn play(
         }
     };
 
-    let call = {
-        let voice_channel_id = match ctx.guild().and_then(|guild| {
-            guild
-                .voice_states
-                .get(&ctx.author().id)
-                .and_then(|voice_state| voice_state.channel_id)
-        }) {
+    let voice_channel_id =
+        match super::voice::voice_channel_of(ctx.cache(), guild_id, ctx.author().id) {
             Some(voice_channel_id) => voice_channel_id,
             None => {
                 let _ = ctx.say("You're not in a voice channel!").await;
                 return Ok(());
             }
         };
-
-        let songbird_manager = match songbird::get(ctx.serenity_context()).await {
-            Some(songbird_manager) => songbird_manager,
-            None => {
-                let _ = ctx.say("Can't get Songbird manager!").await;
-                return Ok(());
-            }
-        };
-
-        match songbird_manager.get(guild_id) {
-            Some(call) => call,
-            None => match songbird_manager.join(guild_id, voice_channel_id).await {
-                Ok(call) => call,
-                Err(e) => {
-                    let _ = ctx.say(format!("Can't join voice channel: {}", e)).await;
-                    return Ok(());
-                }
-            },
+    let call = match super::voice::get_or_join(
+        ctx.serenity_context(),
+        &ctx.data().player_data,
+        guild_id,
+        voice_channel_id,
+    )
+    .await
+    {
+        Ok(call) => call,
+        Err(e) => {
+            let _ = ctx.say(e).await;
+            return Ok(());
         }
     };
 
-    // deafen the bot
-    {
-        let mut call = call.lock().await;
-        if !call.is_deaf() {
-            if let Err(e) = call.deafen(true).await {
-                tracing::warn!("can't deafen the bot: {}", e);
-            }
-        }
-    }
+    let title_rules = ctx.data().title_rules_of(guild_id).await;
+    let guild_settings = ctx.data().guild_settings.get(guild_id).await;
 
     // send initial message
     if let Err(e) = ctx.defer().await {
@@ -85,255 +434,221 @@ pub async fn play(
     let mut reply_handle: Option<ReplyHandle> = None;
     let mut warned_cant_download = false;
 
-    // add global event handlers once per guild
-    if !player_data
-        .call_global_event_handler_added
-        .lock()
-        .await
-        .contains(&guild_id)
-    {
-        let mut call = call.lock().await;
-        call.add_global_event(
-            songbird::Event::Track(songbird::TrackEvent::Play),
-            super::track_event_handler::PlayEventHandler {
-                player_data: ctx.data().player_data.clone(),
-                http: ctx.serenity_context().http.clone(),
-            },
-        );
-        call.add_global_event(
-            songbird::Event::Track(songbird::TrackEvent::End),
-            super::track_event_handler::EndEventHandler {
-                player_data: ctx.data().player_data.clone(),
-            },
-        );
-        // mark the guild as already-added-event-handler
-        player_data
-            .call_global_event_handler_added
-            .lock()
-            .await
-            .insert(guild_id);
-    };
-
-    // create channels for sending track info between threads
-    // - Some(track_info): got a track info
-    // - None: everything completed
-    let (track_info_tx, mut track_info_rx) = tokio::sync::mpsc::channel::<Option<TrackInfo>>(1);
-    // signal from ytdlp thread to break tokio::select! loop
-    let (stop_tx, mut stop_rx) = tokio::sync::oneshot::channel::<String>();
-
-    // spawn yt-dlp thread, push data through channel
-    let yt_dlp_path = ctx.data().config.yt_dlp_path.clone();
-    let text_channel_id = ctx.channel_id();
-    let yt_dlp_thread_handle = tokio::spawn(async move {
-        // create yt-dlp process
-        let mut yt_dlp_process = match Command::new(yt_dlp_path)
-            .arg("-x")
-            .arg("--default-search")
-            .arg("ytsearch")
-            .arg("--skip-download")
-            .arg("--print-json")
-            .arg(&query)
-            .stdout(std::process::Stdio::piped())
-            .spawn()
-        {
-            Ok(yt_dlp_process) => yt_dlp_process,
-            Err(e) => {
-                stop_tx.send(format!("can't run yt-dlp: {}", e)).ok();
-                return;
-            }
-        };
+    let guild_player = guild_player_of(
+        ctx.serenity_context(),
+        &player_data,
+        &ctx.data().guild_settings,
+        &ctx.data().stats,
+        guild_id,
+        &call,
+    )
+    .await;
+    let mut track_filter = TrackFilter::new(&guild_player, title_rules, &guild_settings).await;
 
-        // read yt-dlp output
-        if let Some(stdout) = yt_dlp_process.stdout.take() {
-            let reader: std::io::BufReader<_> = std::io::BufReader::new(stdout);
-            for line in reader.lines() {
-                let line = match line {
-                    Ok(line) => line,
-                    Err(e) => {
-                        error!("can't read yt-dlp output: {}", e);
-                        continue;
-                    }
-                };
-
-                // parse, assign ID and text channel ID
-                let mut track_info: TrackInfo = match serde_json::from_str(line.as_str()) {
-                    Ok(track_info) => track_info,
-                    Err(e) => {
-                        error!("can't parse yt-dlp output: {}", e);
-                        continue;
-                    }
-                };
-                track_info.id = Uuid::new_v4();
-                track_info.text_channel_id = Some(text_channel_id);
+    // resolve the queries in the background, sending tracks through the channel
+    let (resolved_tx, mut resolved_rx) = mpsc::channel::<Resolved>(1);
+    let single_query = queries.len() == 1;
+    let yt_dlp_thread_handle = tokio::spawn(resolve_queries(
+        player_data.clone(),
+        queries,
+        ctx.channel_id(),
+        requester_of(ctx).await,
+        resolved_tx,
+    ));
+    // kill yt-dlp along with the command, however it ends
+    let _yt_dlp_thread_guard = AbortOnDrop(yt_dlp_thread_handle);
 
-                // send new track info
-                if let Err(e) = track_info_tx.send(Some(track_info)).await {
-                    error!("can't send new track to channel: {}", e);
-                    continue;
-                }
-            }
-        };
-
-        // wait for yt-dlp to finish
-        if let Err(e) = yt_dlp_process.wait() {
-            error!("can't wait for yt-dlp to finish: {}", e);
-            stop_tx
-                .send(format!("can't wait for yt-dlp to finish: {}", e))
-                .ok();
-            return;
-        }
-
-        drop(yt_dlp_process);
-
-        // if everything went well
-        if let Err(e) = track_info_tx.send(None).await {
-            error!("can't send None to channel: {}", e);
-        }
-    });
+    // register the import so it can be cancelled on its own
+    let import = guild_player.start_import(ctx.author().id);
+    let cancel_button_id = format!("import-cancel-btn{}", import.id);
+    let mut cancel_presses = ComponentInteractionCollector::new(ctx.serenity_context())
+        .custom_ids(vec![cancel_button_id.clone()])
+        .stream();
 
     // collect incoming track info from channel, download and send to player
     let mut track_count: usize = 0;
-    let mut nuke_signal = ctx.data().player_data.nuke_signal.subscribe();
+    let mut failed_count: usize = 0;
+    let mut duplicate_count: usize = 0;
+    // why tracks were blocked, told only to whoever added them
+    let mut blocked: Vec<String> = Vec::new();
     loop {
         tokio::select! {
-            Some(incoming) = track_info_rx.recv() => {
-                if let Some(track_info) = incoming {
-                    // try to get a playable URL first
-                    let songbird_track = if let Some(direct_url) = track_info.get_playable_url() {
-                        let client = ctx.data().player_data.http_client.clone();
-                        Some(Track::new_with_uuid(HttpRequest::new(client, direct_url).into(), track_info.id))
-                    } else { // else download the track w/ ffmpeg to convert to aac
-                        if !warned_cant_download {
+            resolved = resolved_rx.recv() => {
+                // the resolver went away without saying it's done
+                let Some(resolved) = resolved else { break };
+                match resolved {
+                    Resolved::Track(mut track_info) => {
+                        match track_filter.check(&mut track_info) {
+                            Verdict::Accepted => {}
+                            Verdict::Duplicate => duplicate_count += 1,
+                            Verdict::Blocked(reason) => {
+                                blocked.push(format!("`{}`: {}", track_info.get_title(), reason));
+                                continue;
+                            }
+                            Verdict::DuplicateSkipped => {
+                                duplicate_count += 1;
+                                continue;
+                            }
+                        }
+
+                        // only keep the source URL, the stream is resolved right before playing
+                        if track_info.resolver == ResolverKind::YtDlp
+                            && !track_info.prime_stream_url(&player_data.format_policy)
+                            && !track_info.is_live()
+                            && !warned_cant_download
+                            && player_data.track_cache.peek(&track_info.url, DOWNLOAD_FORMAT).await.is_none()
+                        {
                             warned_cant_download = true;
                             if let Err(e) = ctx.channel_id().say(
                                 ctx.serenity_context().http.clone(),
-                                format!(
-                                    "Can't get a playable URL, downloading the track...\n\
-                                    <@{}> You might want to update `yt-dlp` to the latest version.",
-                                    ctx.data().config.bot_maintainer_uid
-                                ),
+                                "Can't get a playable URL, the track will be downloaded before playing...",
                             ).await { tracing::warn!("can't send message: {}", e); }
                         }
 
-                        let proc = std::process::Command::new(ctx.data().config.yt_dlp_path.clone())
-                            .arg("--ffmpeg-location")
-                            .arg(ctx.data().config.ffmpeg_path.clone())
-                            .arg("--audio-format")
-                            .arg("aac")
-                            .arg("--audio-quality")
-                            .arg("0")
-                            .arg("-x")
-                            .arg("-o")
-                            .arg(track_info.get_download_path(&guild_id))
-                            .arg(&track_info.url)
-                            .spawn();
-                        match proc {
-                            Ok(mut proc) => if let Err(e) = proc.wait() {
-                                error!("can't wait for yt-dlp to finish: {}", e);
-                                continue;
-                            },
-                            Err(e) => {
-                                error!("can't spawn yt-dlp process to download track: {}", e);
-                                continue;
+                        // update message
+                        track_count += 1;
+                        let content = CreateReply::default()
+                            .content(format!(
+                                "Adding `{}` track{} to the queue...",
+                                track_count, if track_count > 1 { "s" } else { "" }
+                            ))
+                            .components(cancel_button(&cancel_button_id));
+                        if let Some(reply_handle) = &reply_handle {
+                            if let Err(e) = reply_handle.edit(ctx, content).await {
+                                tracing::warn!("can't edit reply: {}", e);
                             }
-                        };
-
-                        match track_info.get_input_path(&guild_id) {
-                            Ok(direct_url) => {
-                                let input = songbird::input::File::new(direct_url);
-                                Some(Track::new_with_uuid(input.into(), track_info.id))
+                        } else {
+                            match ctx.send(content).await {
+                                Ok(reply_handle_) => reply_handle = Some(reply_handle_),
+                                Err(e) => tracing::warn!("can't send message: {}", e),
                             }
-                            Err(e) => {
-                                error!("can't get input path: {}", e);
-                                None
-                            },
                         }
-                    };
-
-                    let songbird_track = match songbird_track {
-                        Some(songbird_track) => songbird_track,
-                        None => continue,
-                    };
-
-                    // update message
-                    track_count += 1;
-                    let content = CreateReply::default().content(format!(
-                        "Adding `{}` track{} to the queue...",
-                        track_count, if track_count > 1 { "s" } else { "" }
-                    ));
-                    if let Some(reply_handle) = &reply_handle {
-                        if let Err(e) = reply_handle.edit(ctx, content).await {
-                            tracing::warn!("can't edit reply: {}", e);
+
+                        // add track to the queue
+                        guild_player.enqueue(player_data.clone(), &call, *track_info).await;
+                    }
+                    Resolved::Failed(err) => {
+                        // a single query failing is worth a proper error
+                        if !single_query {
+                            failed_count += 1;
+                            continue;
                         }
-                    } else {
-                        match ctx.send(content).await {
-                            Ok(reply_handle_) => reply_handle = Some(reply_handle_),
-                            Err(e) => tracing::warn!("can't send message: {}", e),
+                        if let Err(e) = ctx.channel_id().send_message(
+                            ctx.serenity_context().http.clone(),
+                            CreateMessage::default().embed(
+                                CreateEmbed::default()
+                                    .title("Error")
+                                    .description(err),
+                            ),
+                        )
+                        .await {
+                            tracing::warn!("can't send message: {}", e);
                         }
+                        if let Some(reply_handle) = &reply_handle {
+                            if let Err(e) = reply_handle.edit(ctx, CreateReply::default().components(vec![])).await {
+                                tracing::warn!("can't edit reply: {}", e);
+                            }
+                        }
+                        break;
                     }
-
-                    { // push to guild -> tracks map
-                        let mut tracks = player_data.guild_2_tracks.lock().await;
-                        if let Some(tracks) = tracks.get_mut(&guild_id) {
-                            tracks.push_back(track_info.clone());
-                        } else {
-                            tracks.insert(guild_id, VecDeque::from([track_info.clone()]));
+                    Resolved::Done => {
+                        // send final update message
+                        let mut content = match track_count {
+                            0 => "No track added to the queue!".to_string(),
+                            1 => "Added `1` track to the queue!".to_string(),
+                            count => format!("Added `{}` tracks to the queue!", count),
+                        };
+                        if failed_count > 0 {
+                            content.push_str(&format!("\n`{}` couldn't be added.", failed_count));
+                        }
+                        match (duplicate_count, guild_settings.duplicate_policy) {
+                            (0, _) => {}
+                            (count, DuplicatePolicy::Reject) => content.push_str(&format!(
+                                "\n`{}` duplicate{} skipped.",
+                                count, if count == 1 { "" } else { "s" }
+                            )),
+                            (count, _) => content.push_str(&format!(
+                                "\n`{}` {} already in the queue.",
+                                count, if count == 1 { "was" } else { "were" }
+                            )),
+                        }
+                        if let Some(reply_handle) = &reply_handle {
+                            if let Err(e) = reply_handle.edit(ctx, CreateReply::default().content(content).components(vec![])).await {
+                                tracing::warn!("can't edit reply: {}", e);
+                            }
+                        } else if let Err(e) = ctx.say(content).await {
+                            tracing::warn!("can't send message: {}", e);
                         };
-                    }
-
-                    // push to track -> guild map
-                    player_data.track_2_guild
-                        .lock()
-                        .await
-                        .insert(track_info.id, guild_id);
 
-                    { // add track to the queue
-                        let mut call = call.lock().await;
-                        let handle = call.enqueue(songbird_track).await;
-                        let _ = handle.make_playable();
+                        break;
+                    }
+                }
+            }
+            Some(press) = cancel_presses.next() => {
+                // only whoever started the import can cancel it
+                if press.user.id != ctx.author().id {
+                    if let Err(e) = press.create_response(
+                        ctx.serenity_context(),
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::default()
+                                .content(format!("Only <@{}> can cancel this.", ctx.author().id))
+                                .ephemeral(true),
+                        ),
+                    ).await {
+                        tracing::warn!("can't respond to cancel button: {}", e);
                     }
-
                     continue;
                 }
-
-                // send final update message
-                let content = match track_count {
-                    0 => "No track added to the queue!".to_string(),
-                    1 => "Added `1` track to the queue!".to_string(),
-                    count => format!("Added `{}` tracks to the queue!", count),
-                };
+                if let Err(e) = press.create_response(ctx.serenity_context(), CreateInteractionResponse::Acknowledge).await {
+                    tracing::warn!("can't respond to cancel button: {}", e);
+                }
+                import.cancel_token.cancel();
+            },
+            _ = import.cancel_token.cancelled() => {
+                // keep what's already in the queue
+                let content = CreateReply::default()
+                    .content(format!(
+                        "Cancelled, added `{}` track{} to the queue.",
+                        track_count, if track_count == 1 { "" } else { "s" }
+                    ))
+                    .components(vec![]);
                 if let Some(reply_handle) = &reply_handle {
-                    if let Err(e) = reply_handle.edit(ctx, CreateReply::default().content(content)).await {
+                    if let Err(e) = reply_handle.edit(ctx, content).await {
                         tracing::warn!("can't edit reply: {}", e);
                     }
-                } else if let Err(e) = ctx.say(content).await {
-                    tracing::warn!("can't send message: {}", e);
-                };
-
-                break;
-            },
-            Ok(err) = &mut stop_rx => {
-                if let Err(e) = ctx.channel_id().send_message(
-                    ctx.serenity_context().http.clone(),
-                    CreateMessage::default().embed(
-                        CreateEmbed::default()
-                            .title("Error")
-                            .description(err),
-                    ),
-                )
-                .await {
+                } else if let Err(e) = ctx.send(content).await {
                     tracing::warn!("can't send message: {}", e);
                 }
                 break;
             },
-            target_guild_id = nuke_signal.recv() => {
-                if let Ok(target_guild_id) = target_guild_id {
-                    if target_guild_id == guild_id {
-                        yt_dlp_thread_handle.abort();
-                        break;
-                    }
-                }
-            }
+        }
+    }
+
+    if !blocked.is_empty() {
+        let mut content = format!(
+            "`{}` track{} blocked in this server:\n",
+            blocked.len(),
+            if blocked.len() == 1 { " is" } else { "s are" }
+        );
+        content.push_str(
+            &blocked
+                .iter()
+                .take(MAX_BLOCKED_SHOWN)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+        if blocked.len() > MAX_BLOCKED_SHOWN {
+            content.push_str(&format!(
+                "\n...and `{}` more",
+                blocked.len() - MAX_BLOCKED_SHOWN
+            ));
+        }
+        if let Err(e) = ctx
+            .send(CreateReply::default().content(content).ephemeral(true))
+            .await
+        {
+            tracing::warn!("can't send message: {}", e);
         }
     }
 
@@ -1,13 +1,61 @@
-use crate::{AppError, Context};
+use super::{checks::ensure_can_control_track, play::enqueue_queries};
+use crate::{
+    data::player_data::{pretty_duration, LoopMode},
+    AppError, Context,
+};
+
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::anyhow;
-use poise::{serenity_prelude::CreateEmbed, CreateReply};
+use poise::{
+    serenity_prelude::{Attachment, CreateAttachment, CreateEmbed},
+    CreateReply,
+};
+use serde::{Deserialize, Serialize};
 use songbird::tracks::PlayMode;
-use uuid::Uuid;
+
+/// Most entries `/queue import` reads from a file.
+const MAX_IMPORT_ENTRIES: usize = 500;
+/// Biggest file `/queue import` downloads.
+const MAX_IMPORT_SIZE: u32 = 1024 * 1024;
+
+/// A track of an exported queue.
+#[derive(Debug, Serialize, Deserialize)]
+struct QueueEntry {
+    url: String,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    duration: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, poise::ChoiceParameter)]
+pub enum ExportFormat {
+    #[default]
+    #[name = "M3U8 playlist"]
+    M3u8,
+    #[name = "JSON"]
+    Json,
+}
+
+/// Show, export or import the queue
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("list", "remove", "export", "import")
+)]
+pub async fn queue(ctx: Context<'_>) -> Result<(), AppError> {
+    list_queue(ctx).await
+}
 
 /// List all tracks in the queue
 #[poise::command(prefix_command, slash_command, guild_only)]
-pub async fn queue(ctx: Context<'_>) -> Result<(), AppError> {
+pub async fn list(ctx: Context<'_>) -> Result<(), AppError> {
+    list_queue(ctx).await
+}
+
+async fn list_queue(ctx: Context<'_>) -> Result<(), AppError> {
     let guild_id = match ctx.guild_id() {
         Some(guild_id) => guild_id,
         _ => {
@@ -18,44 +66,21 @@ pub async fn queue(ctx: Context<'_>) -> Result<(), AppError> {
         }
     };
 
-    let tracks = ctx
-        .data()
-        .player_data
-        .clone()
-        .guild_2_tracks
-        .lock()
-        .await
-        .get(&guild_id)
-        .cloned()
-        .unwrap_or_default();
-
-    // get playing track id
-    let playing_track_id: Option<Uuid> = 'scoped: {
-        let songbird_manager = match songbird::get(ctx.serenity_context()).await {
-            Some(songbird_manager) => songbird_manager.clone(),
-            _ => {
-                return Err(AppError::from(anyhow!(
-                    "commands::player::queue: songbird not loaded"
-                )))
-            }
-        };
-        let call = match songbird_manager.get(guild_id) {
-            Some(call) => call,
-            _ => break 'scoped None,
-        };
-        let track_handle = call.lock().await.queue().current();
+    let (tracks, loop_mode) = match ctx.data().player_data.guild_player(guild_id).await {
+        Some(guild_player) => (guild_player.tracks().await, guild_player.loop_mode().await),
+        None => (Vec::new(), LoopMode::Off),
+    };
 
-        // make sure it's actually playing
-        if let Some(track_handle) = track_handle {
-            let is_playing = match track_handle.get_info().await {
-                Ok(info) => info.playing == PlayMode::Play,
-                Err(_) => break 'scoped None,
-            };
-            if is_playing {
-                break 'scoped Some(track_handle.uuid());
+    // get playing track id and position, making sure it's actually playing
+    let (playing_track_id, position_in_sec, paused) = match tracks.first() {
+        Some((track_handle, _)) => match track_handle.get_info().await {
+            Ok(info) if info.playing == PlayMode::Play => {
+                (Some(track_handle.uuid()), info.position.as_secs(), false)
             }
-        }
-        None
+            Ok(info) if info.playing == PlayMode::Pause => (None, info.position.as_secs(), true),
+            _ => (None, 0, false),
+        },
+        None => (None, 0, false),
     };
 
     if tracks.is_empty() {
@@ -65,34 +90,111 @@ pub async fn queue(ctx: Context<'_>) -> Result<(), AppError> {
         return Ok(());
     }
 
+    // when each track starts, in seconds from now, or why it's unknown
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+    let mut starts_in: Result<u64, &str> = Ok(0);
+    let mut remaining_in_sec = 0;
+    let mut has_unknown_lengths = false;
+    let etas = tracks
+        .iter()
+        .enumerate()
+        .map(|(index, (_, track_info))| {
+            let length = match track_info.duration_in_sec {
+                Some(_) if track_info.is_live() => None,
+                Some(duration_in_sec) if index == 0 => {
+                    Some(duration_in_sec.saturating_sub(position_in_sec))
+                }
+                duration_in_sec => duration_in_sec,
+            };
+            let eta = match (index, starts_in, length) {
+                (0, _, Some(length)) if loop_mode != LoopMode::Track && !paused => {
+                    format!("ends <t:{}:R>", now + length)
+                }
+                (0, _, _) => String::new(),
+                (_, Ok(starts_in), _) => format!("starts <t:{}:R>", now + starts_in),
+                (_, Err(reason), _) => format!("starts {}", reason),
+            };
+
+            match length {
+                Some(length) => remaining_in_sec += length,
+                None => has_unknown_lengths = true,
+            }
+            starts_in = match (starts_in, length) {
+                _ if index == 0 && loop_mode == LoopMode::Track => {
+                    Err("once the current track stops looping")
+                }
+                (Ok(starts_in), Some(length)) => Ok(starts_in + length),
+                (Ok(_), None) => Err("after a track of unknown length"),
+                (Err(reason), _) => Err(reason),
+            };
+            eta
+        })
+        .collect::<Vec<_>>();
+
+    let mut summary = match loop_mode {
+        LoopMode::Track => "🔂 Looping the current track".to_string(),
+        LoopMode::Queue => format!(
+            "🔁 Looping the queue, one round lasts `{}`",
+            pretty_duration(
+                tracks
+                    .iter()
+                    .filter(|(_, track_info)| !track_info.is_live())
+                    .filter_map(|(_, track_info)| track_info.duration_in_sec)
+                    .sum()
+            )
+        ),
+        LoopMode::Off => format!("Remaining: `{}`", pretty_duration(remaining_in_sec)),
+    };
+    if has_unknown_lengths {
+        summary.push_str(", plus livestreams and tracks of unknown length");
+    }
+    if paused {
+        summary.push_str("\n⏸️ Paused, times assume it resumes now");
+    }
+
     ctx.send(CreateReply::default().embed({
         let mut thumbnail = None;
-        let mut embed = CreateEmbed::default().title("Queue").fields(
-            tracks
-                .iter()
-                .map(|track_info| {
-                    (
-                        format!(
-                            "{}{}",
-                            match track_info.id == playing_track_id.unwrap_or_default() {
-                                true => {
-                                    thumbnail.clone_from(&track_info.thumbnail);
-                                    "▶️  "
+        let mut embed = CreateEmbed::default()
+            .title("Queue")
+            .description(summary)
+            .fields(
+                tracks
+                    .iter()
+                    .zip(etas)
+                    .map(|((track_handle, track_info), eta)| {
+                        (
+                            format!(
+                                "{}{}",
+                                match Some(track_handle.uuid()) == playing_track_id {
+                                    true => {
+                                        thumbnail.clone_from(&track_info.thumbnail);
+                                        "▶️  "
+                                    }
+                                    false => "",
+                                },
+                                track_info.get_title()
+                            ),
+                            format!(
+                                "{} | [Source]({}){}{}",
+                                track_info.get_pretty_description(),
+                                track_info.url,
+                                match track_info.requester_name() {
+                                    Some(name) => format!(" | 👤 {}", name),
+                                    None => String::new(),
+                                },
+                                match eta.is_empty() {
+                                    true => String::new(),
+                                    false => format!(" | {}", eta),
                                 }
-                                false => "",
-                            },
-                            track_info.get_title()
-                        ),
-                        format!(
-                            "{} | [Source]({})",
-                            track_info.get_pretty_description(),
-                            track_info.url
-                        ),
-                        false,
-                    )
-                })
-                .collect::<Vec<_>>(),
-        );
+                            ),
+                            false,
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            );
         if let Some(thumbnail) = thumbnail {
             embed = embed.thumbnail(thumbnail);
         };
@@ -108,3 +210,257 @@ pub async fn queue(ctx: Context<'_>) -> Result<(), AppError> {
 
     Ok(())
 }
+
+/// Take a track out of the queue, anyone can remove the tracks they asked for
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Its position in /queue, the playing track being 1"]
+    #[min = 2]
+    position: usize,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    if position < 2 {
+        if let Err(e) = ctx.say("Use `/skip` for the playing track!").await {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    let guild_player = ctx.data().player_data.guild_player(guild_id).await;
+    let track = match &guild_player {
+        Some(guild_player) => guild_player.tracks().await.into_iter().nth(position - 1),
+        None => None,
+    };
+    let (guild_player, (track_handle, track_info)) = match (guild_player, track) {
+        (Some(guild_player), Some(track)) => (guild_player, track),
+        _ => {
+            if let Err(e) = ctx.say("There's no track at that position!").await {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    if let Err(reason) =
+        ensure_can_control_track(ctx.cache(), guild_id, ctx.author().id, &track_info)
+    {
+        if let Err(e) = ctx.say(reason).await {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    let content = match guild_player.remove(track_handle.uuid()).await {
+        Some(track_info) => format!("Removed `{}` from the queue.", track_info.get_title()),
+        None => "That track isn't in the queue anymore!".to_string(),
+    };
+    if let Err(e) = ctx.say(content).await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Save the queue as a file, to share it or edit it
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn export(
+    ctx: Context<'_>,
+    #[description = "File format, M3U8 by default"] format: Option<ExportFormat>,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let tracks = match ctx.data().player_data.guild_player(guild_id).await {
+        Some(guild_player) => guild_player.tracks().await,
+        None => Vec::new(),
+    };
+    if tracks.is_empty() {
+        if let Err(e) = ctx.say("It's empty!").await {
+            tracing::warn!("can't send message 'queue is empty': {}", e);
+        }
+        return Ok(());
+    }
+
+    let attachment = match format.unwrap_or_default() {
+        ExportFormat::M3u8 => {
+            let mut content = "#EXTM3U\n".to_string();
+            for (_, track_info) in &tracks {
+                let duration = match track_info.duration_in_sec {
+                    Some(duration_in_sec) if !track_info.is_live() => duration_in_sec as i64,
+                    _ => -1,
+                };
+                content.push_str(&format!(
+                    "#EXTINF:{},{}\n{}\n",
+                    duration,
+                    track_info.get_title().replace('\n', " "),
+                    track_info.url
+                ));
+            }
+            CreateAttachment::bytes(content, "queue.m3u8")
+        }
+        ExportFormat::Json => {
+            let entries = tracks
+                .iter()
+                .map(|(_, track_info)| QueueEntry {
+                    url: track_info.url.clone(),
+                    title: Some(track_info.get_title()),
+                    duration: track_info.duration_in_sec,
+                })
+                .collect::<Vec<_>>();
+            let content = serde_json::to_vec_pretty(&entries).map_err(|e| {
+                AppError::from(anyhow!(
+                    "commands::player::queue: can't serialize the queue: {}",
+                    e
+                ))
+            })?;
+            CreateAttachment::bytes(content, "queue.json")
+        }
+    };
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "`{}` track{}",
+                tracks.len(),
+                if tracks.len() == 1 { "" } else { "s" }
+            ))
+            .attachment(attachment),
+    )
+    .await
+    .map_err(|e| {
+        AppError::from(anyhow!(
+            "commands::player::queue: can't send message: {}",
+            e
+        ))
+    })?;
+
+    Ok(())
+}
+
+/// Read the source URLs of an exported queue: JSON, M3U8, or one URL per line.
+fn parse_queue_file(filename: &str, content: &str) -> Result<Vec<String>, String> {
+    let is_json =
+        filename.to_lowercase().ends_with(".json") || content.trim_start().starts_with('[');
+    if is_json {
+        // plain lists of URLs are fine too
+        return match serde_json::from_str::<Vec<QueueEntry>>(content) {
+            Ok(entries) => Ok(entries.into_iter().map(|entry| entry.url).collect()),
+            Err(_) => serde_json::from_str::<Vec<String>>(content)
+                .map_err(|e| format!("That's not an exported queue: {}", e)),
+        };
+    }
+
+    // M3U directives and comments start with '#'
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Add the tracks of an exported queue, or of a list of URLs
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn import(
+    ctx: Context<'_>,
+    #[description = "M3U8 or JSON file from /queue export, or a text file with one URL per line"]
+    file: Attachment,
+) -> Result<(), AppError> {
+    // downloading and parsing the file can take longer than Discord waits
+    if let Err(e) = ctx.defer().await {
+        return Err(AppError::from(anyhow!("can't send defer msg: {}", e)));
+    }
+
+    if file.size > MAX_IMPORT_SIZE {
+        if let Err(e) = ctx.say("That file is too big!").await {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    let content = file.download().await.map_err(|e| {
+        AppError::from(anyhow!(
+            "commands::player::queue: can't download {}: {}",
+            file.filename,
+            e
+        ))
+    })?;
+    let content = String::from_utf8_lossy(&content);
+
+    let urls = match parse_queue_file(&file.filename, &content) {
+        Ok(urls) if urls.is_empty() => {
+            if let Err(e) = ctx.say("There's nothing to import in that file!").await {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+        Ok(urls) if urls.len() > MAX_IMPORT_ENTRIES => {
+            if let Err(e) = ctx
+                .say(format!(
+                    "That's too many tracks, up to `{}` can be imported at once!",
+                    MAX_IMPORT_ENTRIES
+                ))
+                .await
+            {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+        Ok(urls) => urls,
+        Err(reason) => {
+            if let Err(e) = ctx.say(reason).await {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    enqueue_queries(ctx, urls).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_exported_json() {
+        let content = r#"[
+            {"url": "https://youtu.be/a", "title": "A", "duration": 120},
+            {"url": "https://youtu.be/b"}
+        ]"#;
+        assert_eq!(
+            parse_queue_file("queue.json", content).unwrap(),
+            ["https://youtu.be/a", "https://youtu.be/b"]
+        );
+    }
+
+    #[test]
+    fn parse_json_list_of_urls() {
+        let content = r#"["https://youtu.be/a", "https://youtu.be/b"]"#;
+        assert_eq!(
+            parse_queue_file("urls.txt", content).unwrap(),
+            ["https://youtu.be/a", "https://youtu.be/b"]
+        );
+    }
+
+    #[test]
+    fn parse_m3u_skips_directives() {
+        let content = "#EXTM3U\n#EXTINF:120,A\nhttps://youtu.be/a\n\n  https://youtu.be/b  \n";
+        assert_eq!(
+            parse_queue_file("queue.m3u8", content).unwrap(),
+            ["https://youtu.be/a", "https://youtu.be/b"]
+        );
+    }
+
+    #[test]
+    fn parse_broken_json() {
+        assert!(parse_queue_file("queue.json", "[{\"title\": \"A\"}]").is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use super::play::enqueue_queries;
+use crate::{data::player_data::RADIO_PREFIX, AppError, Context};
+
+use anyhow::anyhow;
+use poise::{serenity_prelude::CreateEmbed, CreateReply};
+
+/// Most stations a guild can save.
+const MAX_STATIONS: usize = 25;
+/// Longest name a saved station can have.
+const MAX_STATION_NAME_LEN: usize = 50;
+
+async fn autocomplete_station(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Vec::new(),
+    };
+    let partial = partial.to_lowercase();
+    ctx.data()
+        .guild_settings
+        .get(guild_id)
+        .await
+        .radio_stations
+        .into_keys()
+        .filter(|name| name.to_lowercase().contains(&partial))
+        .collect()
+}
+
+/// Play an internet radio, following the songs it plays
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn radio(
+    ctx: Context<'_>,
+    #[description = "Icecast/Shoutcast stream URL, or the name of a saved station"]
+    #[autocomplete = "autocomplete_station"]
+    station: String,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let station = station.trim();
+    let url = match ctx
+        .data()
+        .guild_settings
+        .get(guild_id)
+        .await
+        .radio_stations
+        .get(station)
+    {
+        Some(url) => url.clone(),
+        None if station.starts_with("http://") || station.starts_with("https://") => {
+            station.to_string()
+        }
+        None => {
+            if let Err(e) = ctx
+                .say("That's neither a URL nor a saved station, see `/stations list`.")
+                .await
+            {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    enqueue_queries(ctx, vec![format!("{}{}", RADIO_PREFIX, url)]).await
+}
+
+/// Manage the radio stations saved in this guild
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("list", "save", "delete"),
+    subcommand_required
+)]
+pub async fn stations(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// List the saved radio stations
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn list(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let radio_stations = ctx.data().guild_settings.get(guild_id).await.radio_stations;
+    if radio_stations.is_empty() {
+        if let Err(e) = ctx
+            .say("No station saved yet, add one with `/stations save`.")
+            .await
+        {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::default().title("Radio stations").description(
+                radio_stations
+                    .iter()
+                    .map(|(name, url)| format!("**{}**: {}", name, url))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        ),
+    )
+    .await
+    .map_err(|e| {
+        AppError::from(anyhow!(
+            "commands::player::radio: can't send message: {}",
+            e
+        ))
+    })?;
+
+    Ok(())
+}
+
+/// Save a radio station for /radio
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn save(
+    ctx: Context<'_>,
+    #[description = "Name to play it with"] name: String,
+    #[description = "Icecast/Shoutcast stream URL"] url: String,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let name = name.trim().to_string();
+    let url = url.trim().to_string();
+    let problem = if name.is_empty() || name.chars().count() > MAX_STATION_NAME_LEN {
+        Some(format!(
+            "Names must be 1 to {} characters long.",
+            MAX_STATION_NAME_LEN
+        ))
+    } else if !url.starts_with("http://") && !url.starts_with("https://") {
+        Some("That's not an HTTP or HTTPS URL.".to_string())
+    } else {
+        None
+    };
+    if let Some(problem) = problem {
+        if let Err(e) = ctx.say(problem).await {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    let mut saved = false;
+    ctx.data()
+        .guild_settings
+        .update(guild_id, |settings| {
+            if settings.radio_stations.contains_key(&name)
+                || settings.radio_stations.len() < MAX_STATIONS
+            {
+                settings.radio_stations.insert(name.clone(), url.clone());
+                saved = true;
+            }
+        })
+        .await;
+
+    let content = match saved {
+        true => format!("Saved **{}**, play it with `/radio {}`", name, name),
+        false => format!(
+            "There are already `{}` stations, delete some first!",
+            MAX_STATIONS
+        ),
+    };
+    if let Err(e) = ctx.say(content).await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Delete a saved radio station
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "Name of the station"]
+    #[autocomplete = "autocomplete_station"]
+    name: String,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let mut deleted = false;
+    ctx.data()
+        .guild_settings
+        .update(guild_id, |settings| {
+            deleted = settings.radio_stations.remove(name.trim()).is_some();
+        })
+        .await;
+
+    let content = match deleted {
+        true => format!("Deleted **{}**", name.trim()),
+        false => "There's no station with that name.".to_string(),
+    };
+    if let Err(e) = ctx.say(content).await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use super::voice;
+use crate::data::{
+    guild_settings::GuildSettingsStore,
+    player_data::{GuildPlayer, PlayerData},
+    Data,
+};
+
+use std::{sync::Arc, time::Duration};
+
+use poise::serenity_prelude::{
+    async_trait, ChannelId, Context as SerenityContext, GuildId, VoiceState,
+};
+use songbird::{
+    events::context_data::{DisconnectKind, DisconnectReason},
+    model::CloseCode,
+    Call,
+};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// How long to wait before each attempt to rejoin after losing the voice
+/// connection.
+const REJOIN_DELAYS: [Duration; 5] = [
+    Duration::from_secs(1),
+    Duration::from_secs(3),
+    Duration::from_secs(10),
+    Duration::from_secs(30),
+    Duration::from_secs(60),
+];
+
+/// Where to tell a guild about its voice connection: the announcement
+/// channel, or wherever the playing track was asked for.
+async fn text_channel_of(
+    guild_player: &GuildPlayer,
+    guild_settings: &GuildSettingsStore,
+    guild_id: GuildId,
+) -> Option<ChannelId> {
+    let announce_channel_id = guild_settings.get(guild_id).await.announce_channel_id;
+    match announce_channel_id {
+        Some(channel_id) => Some(channel_id),
+        None => guild_player.current().await?.1.text_channel_id,
+    }
+}
+
+/// Rejoin the voice channel of a call whose connection songbird gave up
+/// on, the playing track going on from where it was.
+async fn rejoin(
+    ctx: SerenityContext,
+    player_data: Arc<PlayerData>,
+    guild_player: Arc<GuildPlayer>,
+    guild_settings: Arc<GuildSettingsStore>,
+    guild_id: GuildId,
+    call: Arc<Mutex<Call>>,
+) {
+    guild_player.pause_for_leave().await;
+
+    let mut last_channel_id = None;
+    for (attempt, delay) in REJOIN_DELAYS.iter().enumerate() {
+        tokio::select! {
+            _ = tokio::time::sleep(*delay) => {}
+            _ = guild_player.cancel_token.cancelled() => return,
+        }
+
+        // follows the bot being moved, and is gone once it's disconnected
+        let channel_id = match call.lock().await.current_channel() {
+            Some(channel_id) => ChannelId::new(channel_id.0.get()),
+            None => return,
+        };
+        last_channel_id = Some(channel_id);
+        match voice::join(&ctx, &player_data, guild_id, channel_id).await {
+            Ok(_) => {
+                info!("rejoined voice channel {} of {}", channel_id, guild_id);
+                return;
+            }
+            Err(e) => warn!(
+                "can't rejoin voice channel {} of {} (attempt {}): {}",
+                channel_id,
+                guild_id,
+                attempt + 1,
+                e
+            ),
+        }
+    }
+
+    let content = match last_channel_id {
+        Some(channel_id) => format!(
+            "🔌 Lost the connection to <#{}> and couldn't get it back. \
+            `/join` to pick up where it stopped.",
+            channel_id
+        ),
+        None => "🔌 Lost the voice connection and couldn't get it back. \
+            `/join` to pick up where it stopped."
+            .to_string(),
+    };
+    match text_channel_of(&guild_player, &guild_settings, guild_id).await {
+        Some(text_channel_id) => {
+            if let Err(e) = text_channel_id.say(&ctx.http, content).await {
+                warn!("can't send message: {}", e);
+            }
+        }
+        None => warn!(
+            "no text channel to tell {} about the voice connection",
+            guild_id
+        ),
+    }
+}
+
+/// Rejoins when songbird can't keep the voice connection up by itself, like
+/// when Discord restarts a voice server or the region of a channel changes.
+#[derive(Debug)]
+pub struct DisconnectHandler {
+    pub ctx: SerenityContext,
+    pub player_data: Arc<PlayerData>,
+    pub guild_player: Arc<GuildPlayer>,
+    pub guild_settings: Arc<GuildSettingsStore>,
+    pub guild_id: GuildId,
+    pub call: Arc<Mutex<Call>>,
+}
+
+#[async_trait]
+impl songbird::EventHandler for DisconnectHandler {
+    async fn act(&self, ctx: &songbird::EventContext<'_>) -> Option<songbird::Event> {
+        match ctx {
+            songbird::EventContext::DriverDisconnect(data) => {
+                // leaving, being disconnected by someone and old connections
+                // dying aren't lost connections, failing to join is
+                // reported to whoever asked
+                let reason = data.reason?;
+                if data.kind == DisconnectKind::Connect
+                    || matches!(
+                        reason,
+                        DisconnectReason::Requested
+                            | DisconnectReason::WsClosed(Some(CloseCode::Disconnected))
+                    )
+                {
+                    return None;
+                }
+                warn!(
+                    "lost voice connection of {} ({:?}, {:?}), rejoining",
+                    self.guild_id, data.kind, reason
+                );
+                tokio::spawn(rejoin(
+                    self.ctx.clone(),
+                    self.player_data.clone(),
+                    self.guild_player.clone(),
+                    self.guild_settings.clone(),
+                    self.guild_id,
+                    self.call.clone(),
+                ));
+            }
+            songbird::EventContext::DriverReconnect(_) => {
+                info!("voice connection of {} is back", self.guild_id);
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Notice the bot being disconnected from its voice channel by someone
+/// else, and keep the playing track where it was for when it joins again.
+pub async fn handle_voice_state_update(
+    ctx: &SerenityContext,
+    data: &Data,
+    old: Option<&VoiceState>,
+    new: &VoiceState,
+) {
+    if new.user_id != ctx.cache.current_user().id || new.channel_id.is_some() {
+        return;
+    }
+    let guild_id = match new.guild_id {
+        Some(guild_id) => guild_id,
+        None => return,
+    };
+    let guild_player = match data.player_data.guild_player(guild_id).await {
+        Some(guild_player) => guild_player,
+        None => return,
+    };
+    // /leave and such
+    if guild_player.take_leaving().await {
+        return;
+    }
+    if guild_player.current().await.is_none() {
+        return;
+    }
+
+    info!("got disconnected from voice in {}", guild_id);
+    guild_player.pause_for_leave().await;
+    let content = match old.and_then(|old| old.channel_id) {
+        Some(channel_id) => format!(
+            "👋 Got disconnected from <#{}>, the queue is kept until `/join` or `/play`.",
+            channel_id
+        ),
+        None => "👋 Got disconnected, the queue is kept until `/join` or `/play`.".to_string(),
+    };
+    if let Some(text_channel_id) =
+        text_channel_of(&guild_player, &data.guild_settings, guild_id).await
+    {
+        if let Err(e) = text_channel_id.say(&ctx.http, content).await {
+            warn!("can't send message: {}", e);
+        }
+    }
+}
@@ -9,7 +9,6 @@ pub async fn restart(ctx: Context<'_>) -> Result<(), AppError> {
         tracing::warn!("can't send message 'restarting': {}", e);
     }
 
-    Command::new("/proc/self/exe").exec();
-
-    Ok(())
+    // `exec` only returns if it failed to replace the process
+    Err(AppError::from(Command::new("/proc/self/exe").exec()))
 }
@@ -0,0 +1,367 @@
+use super::{
+    play::{enqueue_unattended, guild_player_of, requester_of, TrackFilter},
+    time::{parse_time, parse_utc_offset},
+    voice::{ensure_can_connect, get_or_join, voice_channel_of},
+};
+use crate::{
+    data::{
+        guild_settings::GuildSettingsStore,
+        player_data::{PlayerData, Requester},
+        schedules::{ScheduleStore, ScheduledJob},
+        stats::StatsStore,
+        title_rules::TitleRules,
+        Data,
+    },
+    AppError, Context,
+};
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::Utc;
+use poise::{
+    serenity_prelude::{ChannelType, Context as SerenityContext, CreateEmbed, GuildChannel},
+    CreateReply,
+};
+use tracing::{info, warn};
+
+/// Jobs later than this, like after the bot was down for a while, are
+/// reported as missed instead of starting out of the blue.
+const MAX_LATENESS_IN_SEC: u64 = 15 * 60;
+
+/// What the scheduler needs from [`Data`], which the framework owns.
+struct Scheduler {
+    ctx: SerenityContext,
+    player_data: Arc<PlayerData>,
+    guild_settings: Arc<GuildSettingsStore>,
+    stats: Arc<StatsStore>,
+    title_rules: Arc<TitleRules>,
+    schedules: Arc<ScheduleStore>,
+}
+
+fn now_in_sec() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+impl Scheduler {
+    /// Start the jobs as they come due, forever.
+    async fn run(self: Arc<Self>) {
+        loop {
+            for job in self.schedules.take_due(now_in_sec()).await {
+                tokio::spawn(self.clone().run_job(job));
+            }
+
+            // jobs added or cancelled meanwhile wake the scheduler up
+            let wait_in_sec = match self.schedules.next_start().await {
+                Some(starts_at) => starts_at.saturating_sub(now_in_sec()),
+                None => 60 * 60,
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(wait_in_sec)) => {}
+                _ = self.schedules.changed.notified() => {}
+            }
+        }
+    }
+
+    async fn run_job(self: Arc<Self>, job: ScheduledJob) {
+        let (http, text_channel_id) = (self.ctx.http.clone(), job.text_channel_id);
+        let say = |content: String| {
+            let http = http.clone();
+            async move {
+                if let Err(e) = text_channel_id.say(http, content).await {
+                    warn!("can't send message: {}", e);
+                }
+            }
+        };
+
+        let late_by = now_in_sec().saturating_sub(job.starts_at);
+        if late_by > MAX_LATENESS_IN_SEC {
+            info!("missed scheduled job {} of guild {}", job.id, job.guild_id);
+            say(format!(
+                "⏰ Missed the playback of `{}` scheduled by <@{}> for <t:{}:F>, the bot was down.",
+                job.query, job.user_id, job.starts_at
+            ))
+            .await;
+            return;
+        }
+
+        let call = match get_or_join(
+            &self.ctx,
+            &self.player_data,
+            job.guild_id,
+            job.voice_channel_id,
+        )
+        .await
+        {
+            Ok(call) => call,
+            Err(e) => {
+                say(format!(
+                    "⏰ Can't start the playback scheduled by <@{}>: {}",
+                    job.user_id, e
+                ))
+                .await;
+                return;
+            }
+        };
+        let guild_player = guild_player_of(
+            &self.ctx,
+            &self.player_data,
+            &self.guild_settings,
+            &self.stats,
+            job.guild_id,
+            &call,
+        )
+        .await;
+        let guild_settings = self.guild_settings.get(job.guild_id).await;
+        let title_rules = self.title_rules.of_guild(job.guild_id, &guild_settings);
+        let track_filter = TrackFilter::new(&guild_player, title_rules, &guild_settings).await;
+
+        say(format!(
+            "⏰ Starting the playback scheduled by <@{}> in <#{}>: `{}`",
+            job.user_id, job.voice_channel_id, job.query
+        ))
+        .await;
+        let (added, _) = enqueue_unattended(
+            self.player_data.clone(),
+            &guild_player,
+            &call,
+            track_filter,
+            vec![job.query.clone()],
+            Requester {
+                id: job.user_id,
+                name: job.user_name.clone(),
+            },
+            job.text_channel_id,
+        )
+        .await;
+        match added {
+            0 => say(format!("No track found for `{}`!", job.query)).await,
+            1 => say("Added `1` track to the queue!".to_string()).await,
+            added => say(format!("Added `{}` tracks to the queue!", added)).await,
+        }
+    }
+}
+
+/// Run the jobs of /schedule in the background, including the ones saved
+/// before a restart.
+pub fn start_scheduler(ctx: &SerenityContext, data: &Data) {
+    let scheduler = Arc::new(Scheduler {
+        ctx: ctx.clone(),
+        player_data: data.player_data.clone(),
+        guild_settings: data.guild_settings.clone(),
+        stats: data.stats.clone(),
+        title_rules: data.title_rules.clone(),
+        schedules: data.schedules.clone(),
+    });
+    tokio::spawn(scheduler.run());
+}
+
+/// Play something at a set time
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("add", "list", "cancel"),
+    subcommand_required
+)]
+pub async fn schedule(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Join a voice channel and play something at a set time
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    check = "super::checks::can_control"
+)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Like `21:30`, `2024-12-31 21:30` or `in 2h`"] time: String,
+    #[description = "URLs supported by `yt-dlp` or YT search query"] query: String,
+    #[description = "Voice channel to play in, yours by default"]
+    #[channel_types("Voice", "Stage")]
+    channel: Option<GuildChannel>,
+    #[description = "Timezone of the time, like `+07:00`, UTC by default"] utc_offset: Option<
+        String,
+    >,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let voice_channel_id = match channel {
+        Some(channel) if !matches!(channel.kind, ChannelType::Voice | ChannelType::Stage) => {
+            if let Err(e) = ctx.say("That's not a voice channel!").await {
+                warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+        Some(channel) => channel.id,
+        None => match voice_channel_of(ctx.cache(), guild_id, ctx.author().id) {
+            Some(voice_channel_id) => voice_channel_id,
+            None => {
+                if let Err(e) = ctx.say("Pick a voice channel, or join one first!").await {
+                    warn!("can't send message: {}", e);
+                }
+                return Ok(());
+            }
+        },
+    };
+    let member = match ctx.author_member().await {
+        Some(member) => member.into_owned(),
+        None => {
+            if let Err(e) = ctx.say("Can't find who you are, try again later.").await {
+                warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+    if let Err(reason) = ensure_can_connect(ctx.cache(), guild_id, voice_channel_id, &member) {
+        if let Err(e) = ctx.say(reason).await {
+            warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    let offset = match parse_utc_offset(utc_offset.as_deref().unwrap_or_default()) {
+        Some(offset) => offset,
+        None => {
+            if let Err(e) = ctx
+                .say("Can't read that timezone, try `+07:00` or `-5`!")
+                .await
+            {
+                warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+    let starts_at = match parse_time(&time, offset, Utc::now()) {
+        Ok(starts_at) => starts_at,
+        Err(e) => {
+            if let Err(e) = ctx.say(e).await {
+                warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    let requester = requester_of(ctx).await;
+    let job = ScheduledJob {
+        id: 0,
+        guild_id,
+        voice_channel_id,
+        text_channel_id: ctx.channel_id(),
+        user_id: requester.id,
+        user_name: requester.name,
+        query: query.trim().to_string(),
+        starts_at,
+    };
+    let content = match ctx.data().schedules.add(job).await {
+        Ok(job) => format!(
+            "⏰ `#{}` Playing `{}` in <#{}> on <t:{}:F> (<t:{}:R>).",
+            job.id, job.query, job.voice_channel_id, job.starts_at, job.starts_at
+        ),
+        Err(e) => e,
+    };
+    if let Err(e) = ctx.say(content).await {
+        warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
+
+/// List the scheduled playbacks
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn list(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let jobs = ctx.data().schedules.of_guild(guild_id).await;
+    if jobs.is_empty() {
+        if let Err(e) = ctx.say("Nothing is scheduled.").await {
+            warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    let description = jobs
+        .iter()
+        .map(|job| {
+            format!(
+                "`#{}` <t:{}:F> in <#{}> by <@{}>\n`{}`",
+                job.id, job.starts_at, job.voice_channel_id, job.user_id, job.query
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if let Err(e) = ctx
+        .send(
+            CreateReply::default().embed(
+                CreateEmbed::default()
+                    .title("Scheduled")
+                    .description(description),
+            ),
+        )
+        .await
+    {
+        warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Whether the author can manage the guild, to cancel other people's jobs.
+async fn author_manages_guild(ctx: Context<'_>) -> bool {
+    let member = match ctx.author_member().await {
+        Some(member) => member.into_owned(),
+        None => return false,
+    };
+    let guild = match ctx.guild() {
+        Some(guild) => guild,
+        None => return false,
+    };
+    guild
+        .channels
+        .get(&ctx.channel_id())
+        .is_some_and(|channel| guild.user_permissions_in(channel, &member).manage_guild())
+}
+
+/// Cancel a scheduled playback
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn cancel(
+    ctx: Context<'_>,
+    #[description = "Its number, from /schedule list"] id: u32,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let author_id = ctx.author().id;
+    let manages_guild = author_manages_guild(ctx).await;
+    let content = match ctx
+        .data()
+        .schedules
+        .cancel(guild_id, id, |job| {
+            job.user_id == author_id || manages_guild
+        })
+        .await
+    {
+        Ok(job) => format!("Cancelled `#{}`: `{}`", job.id, job.query),
+        Err(e) => e,
+    };
+    if let Err(e) = ctx.say(content).await {
+        warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
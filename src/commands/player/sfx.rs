@@ -0,0 +1,313 @@
+use super::voice::{get_or_join, voice_channel_of};
+use crate::{
+    data::{
+        player_data::probe,
+        sfx::{is_valid_clip_name, CLIP_EXTENSIONS},
+    },
+    AppError, Context,
+};
+
+use std::{io::Cursor, time::Duration};
+
+use anyhow::anyhow;
+use poise::{
+    serenity_prelude::{Attachment, CreateEmbed},
+    CreateReply,
+};
+use songbird::{
+    input::{File, Input},
+    tracks::Track,
+};
+use symphonia::core::probe::Hint;
+
+/// Biggest clip that can be uploaded.
+const MAX_CLIP_SIZE: u32 = 1024 * 1024;
+/// Longest clip that can be uploaded.
+const MAX_CLIP_DURATION: Duration = Duration::from_secs(15);
+
+async fn autocomplete_clip(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Vec::new(),
+    };
+    ctx.data()
+        .sfx
+        .clips(guild_id)
+        .await
+        .into_keys()
+        .filter(|name| name.contains(&partial.to_lowercase()))
+        .take(25)
+        .collect()
+}
+
+async fn autocomplete_guild_clip(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Vec::new(),
+    };
+    ctx.data()
+        .sfx
+        .guild_clips(guild_id)
+        .await
+        .into_keys()
+        .filter(|name| name.contains(&partial.to_lowercase()))
+        .take(25)
+        .collect()
+}
+
+/// Play short clips over the music
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("play_clip", "list_clips", "add_clip", "remove_clip"),
+    subcommand_required
+)]
+pub async fn sfx(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Play a clip without stopping the music
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "play",
+    check = "super::checks::can_control"
+)]
+pub async fn play_clip(
+    ctx: Context<'_>,
+    #[description = "Name of the clip"]
+    #[autocomplete = "autocomplete_clip"]
+    name: String,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let path = match ctx.data().sfx.clips(guild_id).await.remove(name.trim()) {
+        Some(path) => path,
+        None => {
+            if let Err(e) = ctx
+                .say("There's no clip with that name, see `/sfx list`.")
+                .await
+            {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    let voice_channel_id = match voice_channel_of(ctx.cache(), guild_id, ctx.author().id) {
+        Some(voice_channel_id) => voice_channel_id,
+        None => {
+            if let Err(e) = ctx.say("You're not in a voice channel!").await {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+    let call = match get_or_join(
+        ctx.serenity_context(),
+        &ctx.data().player_data,
+        guild_id,
+        voice_channel_id,
+    )
+    .await
+    {
+        Ok(call) => call,
+        Err(e) => {
+            if let Err(e) = ctx.say(e).await {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    // as loud as the music
+    let volume = match ctx.data().player_data.guild_player(guild_id).await {
+        Some(guild_player) => guild_player.volume_percent().await as f32 / 100.0,
+        None => 1.0,
+    };
+    // played next to the queue, which keeps going
+    call.lock()
+        .await
+        .play(Track::new(Input::from(File::new(path))).volume(volume));
+
+    if let Err(e) = ctx.say(format!("🔊 `{}`", name.trim())).await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
+
+/// List the clips that can be played
+#[poise::command(prefix_command, slash_command, guild_only, rename = "list")]
+pub async fn list_clips(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let clips = ctx.data().sfx.clips(guild_id).await;
+    if clips.is_empty() {
+        if let Err(e) = ctx
+            .say("No clips yet, admins can add some with `/sfx add`.")
+            .await
+        {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::default().title("Clips").description(
+                clips
+                    .keys()
+                    .map(|name| format!("`{}`", name))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+        ),
+    )
+    .await
+    .map_err(|e| AppError::from(anyhow!("commands::player::sfx: can't send message: {}", e)))?;
+
+    Ok(())
+}
+
+/// Upload a clip to this guild, replacing the one with the same name
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "add"
+)]
+pub async fn add_clip(
+    ctx: Context<'_>,
+    #[description = "Lowercase letters, digits, - and _"] name: String,
+    #[description = "MP3, OGG, Opus, WAV or FLAC file, up to 15 seconds"] file: Attachment,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let name = name.trim().to_lowercase();
+    let extension = file
+        .filename
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default();
+    let problem = if !is_valid_clip_name(&name) {
+        Some("Names are 1 to 32 lowercase letters, digits, `-` or `_`.".to_string())
+    } else if !CLIP_EXTENSIONS.contains(&extension.as_str()) {
+        Some(format!(
+            "Clips must be one of {}.",
+            CLIP_EXTENSIONS
+                .iter()
+                .map(|extension| format!("`.{}`", extension))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    } else if file.size > MAX_CLIP_SIZE {
+        Some("That file is too big!".to_string())
+    } else {
+        None
+    };
+    if let Some(problem) = problem {
+        if let Err(e) = ctx.say(problem).await {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    let content = file.download().await.map_err(|e| {
+        AppError::from(anyhow!(
+            "commands::player::sfx: can't download {}: {}",
+            file.filename,
+            e
+        ))
+    })?;
+
+    // make sure it plays, and isn't a whole song
+    let probed = {
+        let content = content.clone();
+        let extension = extension.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut hint = Hint::new();
+            hint.with_extension(&extension);
+            probe(Box::new(Cursor::new(content)), hint)
+        })
+        .await
+        .map_err(|e| AppError::from(anyhow!("commands::player::sfx: can't probe clip: {}", e)))?
+    };
+    let problem = match probed {
+        Err(_) => Some("That file can't be played.".to_string()),
+        Ok(probed) if probed.duration_in_sec.unwrap_or(u64::MAX) > MAX_CLIP_DURATION.as_secs() => {
+            Some(format!(
+                "Clips can be up to `{}` seconds long.",
+                MAX_CLIP_DURATION.as_secs()
+            ))
+        }
+        Ok(_) => None,
+    };
+    if let Some(problem) = problem {
+        if let Err(e) = ctx.say(problem).await {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    ctx.data()
+        .sfx
+        .add(guild_id, &name, &extension, &content)
+        .await
+        .map_err(|e| AppError::from(anyhow!("commands::player::sfx: {}", e)))?;
+
+    if let Err(e) = ctx.say(format!("Added `{}`", name)).await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Delete a clip uploaded to this guild
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "remove"
+)]
+pub async fn remove_clip(
+    ctx: Context<'_>,
+    #[description = "Name of the clip"]
+    #[autocomplete = "autocomplete_guild_clip"]
+    name: String,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let removed = ctx
+        .data()
+        .sfx
+        .remove(guild_id, name.trim())
+        .await
+        .map_err(|e| AppError::from(anyhow!("commands::player::sfx: {}", e)))?;
+
+    let content = match removed {
+        true => format!("Removed `{}`", name.trim()),
+        false => "This guild has no clip with that name.".to_string(),
+    };
+    if let Err(e) = ctx.say(content).await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
@@ -2,11 +2,10 @@ use crate::{AppError, Context};
 
 use anyhow::anyhow;
 use poise::{
-    serenity_prelude::{CreateEmbed, CreateEmbedAuthor},
+    serenity_prelude::{CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter},
     CreateReply,
 };
 use tracing::warn;
-use uuid::Uuid;
 
 /// Skip the current track
 #[poise::command(prefix_command, slash_command, guild_only)]
@@ -35,48 +34,22 @@ pub async fn skip(ctx: Context<'_>) -> Result<(), AppError> {
         };
         match songbird_manager.get(guild_id) {
             Some(call) => call,
-            None => match songbird_manager.join(guild_id, ctx.channel_id()).await {
-                Ok(call) => call,
-                Err(e) => {
-                    let _ = ctx.say(format!("Can't join voice channel: {}", e)).await;
-                    return Ok(());
+            None => {
+                if let Err(e) = ctx.say("Not in a voice channel.").await {
+                    tracing::warn!("can't send message 'not in a voice channel': {}", e);
                 }
-            },
-        }
-    };
-
-    // check songbird's queue
-    {
-        let call = call.lock().await;
-        let queue = call.queue();
-        if queue.is_empty() {
-            if let Err(e) = ctx.say("There's no track in the queue!").await {
-                tracing::warn!("can't send message: {}", e);
+                return Ok(());
             }
-            return Ok(());
         }
-        queue.skip().map_err(|e| {
-            AppError::from(anyhow!("commands::player::skip: can't skip track: {}", e))
-        })?;
-    }
+    };
 
-    // check our own playlist queue
-    let mut next_track_id: Option<Uuid> = None;
-    let just_skipped_track = ctx
-        .data()
-        .player_data
-        .guild_2_tracks
-        .lock()
-        .await
-        .get_mut(&guild_id)
-        .and_then(|tracks| {
-            if tracks.len() >= 2 {
-                next_track_id = Some(tracks[1].id);
-            }
-            tracks.pop_front()
-        });
+    // the track about to be skipped
+    let just_skipped_track = match ctx.data().player_data.guild_player(guild_id).await {
+        Some(guild_player) => guild_player.current().await,
+        None => None,
+    };
     let just_skipped_track = match just_skipped_track {
-        Some(track_info) => track_info,
+        Some((_, track_info)) => track_info,
         None => {
             if let Err(e) = ctx.say("There's no track in the queue!").await {
                 tracing::warn!("can't send message: {}", e);
@@ -85,22 +58,23 @@ pub async fn skip(ctx: Context<'_>) -> Result<(), AppError> {
         }
     };
 
-    // replace track in track_2_guild mapping
-    let player_data = ctx.data().player_data.clone();
-    let mut track_2_guild = player_data.track_2_guild.lock().await;
-    track_2_guild.remove(&just_skipped_track.id);
-    if let Some(next_track_id) = next_track_id {
-        track_2_guild.insert(next_track_id, guild_id);
-    }
+    call.lock()
+        .await
+        .queue()
+        .skip()
+        .map_err(|e| AppError::from(anyhow!("commands::player::skip: can't skip track: {}", e)))?;
 
     let mut embed = CreateEmbed::default()
         .author(CreateEmbedAuthor::new("Skipped track"))
         .title(just_skipped_track.get_title())
         .description(just_skipped_track.get_pretty_description())
         .url(&just_skipped_track.url);
-    if let Some(thumbnail) = just_skipped_track.thumbnail {
+    if let Some(thumbnail) = just_skipped_track.thumbnail.clone() {
         embed = embed.thumbnail(thumbnail);
     }
+    if let Some(name) = just_skipped_track.requester_name() {
+        embed = embed.footer(CreateEmbedFooter::new(format!("Requested by {}", name)));
+    }
     ctx.send(CreateReply::default().embed(embed)).await?;
 
     Ok(())
@@ -0,0 +1,187 @@
+use super::time::{parse_duration, MAX_DURATION_IN_SEC};
+use crate::{data::player_data::GuildPlayer, AppError, Context};
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use poise::serenity_prelude::{ChannelId, Context as SerenityContext, GuildId};
+use songbird::tracks::PlayMode;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+use uuid::Uuid;
+
+/// How long the music fades out before the bot leaves.
+const FADE_OUT: Duration = Duration::from_secs(8);
+
+/// Wait for a sleep timer to go off, then fade the music out, stop it and
+/// leave the voice channel.
+async fn run_sleep_timer(
+    ctx: SerenityContext,
+    guild_player: Arc<GuildPlayer>,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    id: Uuid,
+    cancel_token: CancellationToken,
+    in_sec: u64,
+) {
+    let fade_starts_in = Duration::from_secs(in_sec).saturating_sub(FADE_OUT);
+    tokio::select! {
+        _ = tokio::time::sleep(fade_starts_in) => {}
+        _ = cancel_token.cancelled() => return,
+    }
+    let fade_out = FADE_OUT.min(Duration::from_secs(in_sec));
+    if !guild_player.fade_out(fade_out, &cancel_token).await {
+        return;
+    }
+    guild_player.end_sleep_timer(id).await;
+
+    guild_player.stop().await;
+    guild_player.start_leaving().await;
+    match songbird::get(&ctx).await {
+        Some(songbird_manager) => {
+            if let Err(e) = songbird_manager.leave(guild_id).await {
+                warn!("can't leave voice channel: {}", e);
+            }
+        }
+        None => warn!("songbird not loaded"),
+    }
+    if let Err(e) = channel_id
+        .say(&ctx.http, "💤 Sleep timer's up, good night!")
+        .await
+    {
+        warn!("can't send message: {}", e);
+    }
+}
+
+/// Stop the music and leave after a while
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    check = "super::checks::can_control"
+)]
+pub async fn sleep(
+    ctx: Context<'_>,
+    #[description = "Like `30m` or `1h30m`, `end` for the end of the track, `off` to cancel"]
+    duration: Option<String>,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+    let guild_player = match ctx.data().player_data.guild_player(guild_id).await {
+        Some(guild_player) => guild_player,
+        None => {
+            if let Err(e) = ctx.say("Nothing is playing.").await {
+                warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+    let duration = match duration {
+        Some(duration) => duration.trim().to_lowercase(),
+        // no duration, tell when the running timer ends
+        None => {
+            let content = match guild_player.sleep_timer().await {
+                Some(ends_at) => format!("💤 Stopping <t:{}:R>.", ends_at),
+                None => "There's no sleep timer.".to_string(),
+            };
+            if let Err(e) = ctx.say(content).await {
+                warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    let in_sec = match duration.as_str() {
+        "off" | "cancel" => {
+            let content = match guild_player.cancel_sleep_timer().await {
+                true => "Sleep timer cancelled.",
+                false => "There's no sleep timer.",
+            };
+            if let Err(e) = ctx.say(content).await {
+                warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+        "end" | "end-of-track" => {
+            let remaining = match guild_player.current().await {
+                Some((handle, track_info)) if !track_info.is_live() => {
+                    match (handle.get_info().await, track_info.duration_in_sec) {
+                        (Ok(info), Some(duration_in_sec)) if info.playing != PlayMode::End => {
+                            Some(duration_in_sec.saturating_sub(info.position.as_secs()))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            match remaining {
+                Some(remaining) => remaining,
+                None => {
+                    if let Err(e) = ctx
+                        .say("The current track has no known end, give a duration instead!")
+                        .await
+                    {
+                        warn!("can't send message: {}", e);
+                    }
+                    return Ok(());
+                }
+            }
+        }
+        duration => match parse_duration(duration) {
+            Some(in_sec) if in_sec > 0 => in_sec,
+            _ => {
+                if let Err(e) = ctx
+                    .say(format!(
+                        "Can't read that duration, try `30m`, `1h30m` or `end`, up to `{}d`!",
+                        MAX_DURATION_IN_SEC / 86400
+                    ))
+                    .await
+                {
+                    warn!("can't send message: {}", e);
+                }
+                return Ok(());
+            }
+        },
+    };
+
+    let ends_at = match now.checked_add(in_sec) {
+        Some(ends_at) => ends_at,
+        None => {
+            if let Err(e) = ctx.say("That's too far away!").await {
+                warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+    let (id, cancel_token) = guild_player.start_sleep_timer(ends_at).await;
+    tokio::spawn(run_sleep_timer(
+        ctx.serenity_context().clone(),
+        guild_player,
+        guild_id,
+        ctx.channel_id(),
+        id,
+        cancel_token,
+        in_sec,
+    ));
+
+    if let Err(e) = ctx
+        .say(format!(
+            "💤 Stopping <t:{}:R>, `/sleep off` to cancel.",
+            ends_at
+        ))
+        .await
+    {
+        warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use crate::{
+    data::{player_data::pretty_duration, stats::PlayStats},
+    AppError, Context,
+};
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use poise::{serenity_prelude::CreateEmbed, CreateReply};
+
+/// How many entries each list of /stats shows.
+const SHOWN_ENTRIES: usize = 5;
+/// Discord rejects embed fields longer than this.
+const MAX_FIELD_LEN: usize = 1024;
+const MAX_TITLE_LEN: usize = 80;
+
+/// The most played tracks, as lines of an embed field. Tracks get a link
+/// when there's a web page to go to and it fits in the field.
+fn top_tracks_field(play_stats: &PlayStats) -> String {
+    let top_tracks = play_stats.top_tracks(SHOWN_ENTRIES);
+    if top_tracks.is_empty() {
+        return "Nothing yet".to_string();
+    }
+    let max_line_len = MAX_FIELD_LEN / SHOWN_ENTRIES - 1;
+    top_tracks
+        .iter()
+        .map(|(url, track)| {
+            let mut title = track.title.replace(['[', ']', '`'], "");
+            if title.chars().count() > MAX_TITLE_LEN {
+                title = title.chars().take(MAX_TITLE_LEN - 1).collect();
+                title.push('…');
+            }
+            let line = format!("`{}×` [{}]({})", track.plays, title, url);
+            match url.starts_with("https://") || url.starts_with("http://") {
+                true if line.chars().count() <= max_line_len => line,
+                _ => format!("`{}×` {}", track.plays, title),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Plays, listening time and skip rate, as lines of an embed field.
+fn summary_field(play_stats: &PlayStats) -> String {
+    format!(
+        "Tracks played: `{}`\nListening time: `{}`\nSkip rate: `{:.0}%`",
+        play_stats.plays,
+        pretty_duration(play_stats.listened_in_sec),
+        play_stats.skip_rate()
+    )
+}
+
+/// Show what this server listens to
+#[poise::command(prefix_command, slash_command, guild_only, subcommands("server", "me"))]
+pub async fn stats(ctx: Context<'_>) -> Result<(), AppError> {
+    server_stats(ctx).await
+}
+
+/// Most played tracks, top requesters and busiest hours of this server
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn server(ctx: Context<'_>) -> Result<(), AppError> {
+    server_stats(ctx).await
+}
+
+async fn server_stats(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let guild_stats = ctx.data().stats.get(guild_id).await;
+    if guild_stats.overall.plays == 0 {
+        if let Err(e) = ctx.say("Nothing played yet!").await {
+            tracing::warn!("can't send message: {}", e);
+        }
+        return Ok(());
+    }
+
+    let mut requesters = guild_stats.requesters.iter().collect::<Vec<_>>();
+    requesters.sort_by_key(|(_, play_stats)| std::cmp::Reverse(play_stats.plays));
+    let top_requesters = requesters
+        .iter()
+        .take(SHOWN_ENTRIES)
+        .map(|(user_id, play_stats)| {
+            format!(
+                "<@{}> `{}` tracks, `{}`",
+                user_id,
+                play_stats.plays,
+                pretty_duration(play_stats.listened_in_sec)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    // hours are kept in UTC, timestamps show them in everyone's own timezone
+    let today = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+        / 86400
+        * 86400;
+    let mut hours = (0..24u64)
+        .filter(|hour| guild_stats.plays_by_hour[*hour as usize] > 0)
+        .collect::<Vec<_>>();
+    hours.sort_by_key(|hour| std::cmp::Reverse(guild_stats.plays_by_hour[*hour as usize]));
+    let busiest_hours = hours
+        .iter()
+        .take(3)
+        .map(|hour| {
+            format!(
+                "<t:{}:t> `{}` tracks",
+                today + hour * 3600,
+                guild_stats.plays_by_hour[*hour as usize]
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut embed = CreateEmbed::default()
+        .title("Stats")
+        .field("Overall", summary_field(&guild_stats.overall), false)
+        .field("Most played", top_tracks_field(&guild_stats.overall), false);
+    if !top_requesters.is_empty() {
+        embed = embed.field("Top requesters", top_requesters, false);
+    }
+    embed = embed.field("Busiest hours", busiest_hours, false);
+
+    ctx.send(CreateReply::default().embed(embed))
+        .await
+        .map_err(|e| {
+            AppError::from(anyhow!(
+                "commands::player::stats: can't send message: {}",
+                e
+            ))
+        })?;
+
+    Ok(())
+}
+
+/// What you asked this server to play
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn me(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let guild_stats = ctx.data().stats.get(guild_id).await;
+    let play_stats = match guild_stats.requesters.get(&ctx.author().id) {
+        Some(play_stats) => play_stats,
+        None => {
+            if let Err(e) = ctx.say("You haven't played anything here yet!").await {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    // rank among the requesters, by tracks played
+    let rank = guild_stats
+        .requesters
+        .values()
+        .filter(|other| other.plays > play_stats.plays)
+        .count()
+        + 1;
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::default()
+                .title(format!("Stats of {}", ctx.author().name))
+                .description(format!(
+                    "Top requester `#{}` of `{}`",
+                    rank,
+                    guild_stats.requesters.len()
+                ))
+                .field("Your tracks", summary_field(play_stats), false)
+                .field("Most played", top_tracks_field(play_stats), false),
+        ),
+    )
+    .await
+    .map_err(|e| {
+        AppError::from(anyhow!(
+            "commands::player::stats: can't send message: {}",
+            e
+        ))
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::stats::TrackStats;
+
+    #[test]
+    fn top_tracks_fit_in_a_field() {
+        let mut play_stats = PlayStats::default();
+        for (i, url) in [
+            format!("https://example.com/{}", "a".repeat(2000)),
+            "https://example.com/short".to_string(),
+            "local:song.mp3".to_string(),
+            "radio:lofi".to_string(),
+            "fake://track".to_string(),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let track = TrackStats {
+                title: format!("{} [live]", "long title ".repeat(100)),
+                plays: 10 - i as u64,
+            };
+            play_stats.tracks.insert(url, track);
+        }
+
+        let field = top_tracks_field(&play_stats);
+        assert!(field.chars().count() <= MAX_FIELD_LEN);
+        let lines = field.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), SHOWN_ENTRIES);
+        // too long to link
+        assert!(!lines[0].contains("]("));
+        assert!(lines[1].ends_with("](https://example.com/short)"));
+        assert!(lines[1].contains('…'));
+        assert!(lines[2..].iter().all(|line| !line.contains("](")));
+    }
+}
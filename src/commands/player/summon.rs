@@ -0,0 +1,28 @@
+use super::{join::join_channel, voice::voice_channel_of};
+use crate::{AppError, Context};
+
+/// Bring the bot to your voice channel, without stopping the music
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn summon(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => {
+            if let Err(e) = ctx.say("This command must be invoke in a guild!").await {
+                tracing::warn!("can't send message 'guild command only': {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    let channel_id = match voice_channel_of(ctx.cache(), guild_id, ctx.author().id) {
+        Some(channel_id) => channel_id,
+        None => {
+            if let Err(e) = ctx.say("You're not in a voice channel!").await {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+    };
+
+    join_channel(ctx, guild_id, channel_id).await
+}
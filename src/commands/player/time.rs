@@ -0,0 +1,181 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+/// Longest duration [`parse_duration`] takes, in seconds.
+pub const MAX_DURATION_IN_SEC: u64 = 7 * 24 * 60 * 60;
+
+/// Parse a duration like `1h30m` or `45s` into seconds, a bare number
+/// being minutes. Durations over [`MAX_DURATION_IN_SEC`] aren't taken.
+pub fn parse_duration(input: &str) -> Option<u64> {
+    parse_any_duration(input).filter(|seconds| *seconds <= MAX_DURATION_IN_SEC)
+}
+
+fn parse_any_duration(input: &str) -> Option<u64> {
+    let input = input.trim().to_lowercase();
+    if let Ok(minutes) = input.parse::<u64>() {
+        return minutes.checked_mul(60);
+    }
+
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+    for c in input.chars().filter(|c| !c.is_whitespace()) {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'd' => 24 * 60 * 60,
+            'h' => 60 * 60,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        let value = std::mem::take(&mut number).parse::<u64>().ok()?;
+        seconds = seconds.checked_add(value.checked_mul(unit)?)?;
+    }
+    // a number left without a unit, like `1h30`, is ambiguous
+    match number.is_empty() && seconds > 0 {
+        true => Some(seconds),
+        false => None,
+    }
+}
+
+/// Parse a UTC offset like `+7`, `+07:00` or `UTC-5:30`.
+pub fn parse_utc_offset(input: &str) -> Option<FixedOffset> {
+    let input = input.trim().to_uppercase();
+    let input = input.strip_prefix("UTC").unwrap_or(&input);
+    if input.is_empty() {
+        return FixedOffset::east_opt(0);
+    }
+    let (sign, input) = match input.split_at(1) {
+        ("+", input) => (1, input),
+        ("-", input) => (-1, input),
+        _ => return None,
+    };
+    let (hours, minutes) = input.split_once(':').unwrap_or((input, "0"));
+    let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Parse when something should happen into a Unix timestamp: a duration
+/// from now like `2h` or `in 2h`, a time like `21:30` (tomorrow if it
+/// already passed today), or a date and time like `2024-12-31 21:30`.
+/// Times are read in `offset`.
+pub fn parse_time(input: &str, offset: FixedOffset, now: DateTime<Utc>) -> Result<u64, String> {
+    let input = input.trim();
+    let relative = input.strip_prefix("in ").unwrap_or(input);
+    if let Some(seconds) = parse_any_duration(relative) {
+        return match seconds <= MAX_DURATION_IN_SEC {
+            true => (now.timestamp() as u64)
+                .checked_add(seconds)
+                .ok_or("That's too far away!".to_string()),
+            false => Err("That's too far away, give a date instead!".to_string()),
+        };
+    }
+
+    let local_now = now.with_timezone(&offset).naive_local();
+    let date_time = if let Ok(time) = NaiveTime::parse_from_str(input, "%H:%M") {
+        let today = local_now.date().and_time(time);
+        match today > local_now {
+            true => today,
+            false => today + Duration::days(1),
+        }
+    } else if let Ok(date_time) = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M") {
+        date_time
+    } else if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        date.and_time(NaiveTime::MIN)
+    } else {
+        return Err(format!(
+            "Can't read `{}` as a time, try `30m`, `21:30` or `2024-12-31 21:30`!",
+            input
+        ));
+    };
+
+    let timestamp = date_time.and_utc().timestamp() - offset.local_minus_utc() as i64;
+    match timestamp > now.timestamp() {
+        true => Ok(timestamp as u64),
+        false => Err("That's in the past!".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("45s"), Some(45));
+        assert_eq!(parse_duration("1h30m"), Some(5400));
+        assert_eq!(parse_duration("1h 30m"), Some(5400));
+        assert_eq!(parse_duration("2D"), Some(2 * 24 * 3600));
+        assert_eq!(parse_duration("10"), Some(600));
+    }
+
+    #[test]
+    fn parse_bad_durations() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("1h30"), None);
+        assert_eq!(parse_duration("0s"), None);
+        assert_eq!(parse_duration("5w"), None);
+        assert_eq!(parse_duration("8d"), None);
+        assert_eq!(parse_duration("99999999999999999999s"), None);
+        assert_eq!(parse_duration(&u64::MAX.to_string()), None);
+    }
+
+    #[test]
+    fn parse_utc_offsets() {
+        assert_eq!(parse_utc_offset("+7"), FixedOffset::east_opt(7 * 3600));
+        assert_eq!(
+            parse_utc_offset("UTC-5:30"),
+            FixedOffset::east_opt(-(5 * 3600 + 1800))
+        );
+        assert_eq!(parse_utc_offset("utc"), FixedOffset::east_opt(0));
+        assert_eq!(parse_utc_offset("+15"), None);
+        assert_eq!(parse_utc_offset("7"), None);
+    }
+
+    #[test]
+    fn parse_relative_times() {
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let now_timestamp = now().timestamp() as u64;
+        assert_eq!(parse_time("2h", utc, now()), Ok(now_timestamp + 7200));
+        assert_eq!(parse_time("in 30m", utc, now()), Ok(now_timestamp + 1800));
+        assert!(parse_time("30d", utc, now()).is_err());
+    }
+
+    #[test]
+    fn parse_times_of_day() {
+        let plus_two = FixedOffset::east_opt(2 * 3600).unwrap();
+        // 14:00 where it's 14:00 already is tomorrow
+        assert_eq!(
+            parse_time("14:00", plus_two, now()),
+            Ok(now().timestamp() as u64 + 24 * 3600)
+        );
+        assert_eq!(
+            parse_time("15:30", plus_two, now()),
+            Ok(now().timestamp() as u64 + 5400)
+        );
+    }
+
+    #[test]
+    fn parse_dates() {
+        let utc = FixedOffset::east_opt(0).unwrap();
+        assert_eq!(
+            parse_time("2024-12-31 21:30", utc, now()),
+            Ok(Utc
+                .with_ymd_and_hms(2024, 12, 31, 21, 30, 0)
+                .unwrap()
+                .timestamp() as u64)
+        );
+        assert!(parse_time("2024-01-01", utc, now()).is_err());
+        assert!(parse_time("tomorrow", utc, now()).is_err());
+    }
+}
@@ -1,24 +1,83 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use poise::serenity_prelude::{
-    async_trait, ChannelId, CreateEmbed, CreateEmbedAuthor, CreateMessage, Http,
+use poise::serenity_prelude::{async_trait, ChannelId, GuildId, Http};
+use songbird::{
+    input::AudioStreamError,
+    tracks::{PlayError, PlayMode},
+    Call,
 };
-use songbird::tracks::PlayMode;
+use tokio::sync::Mutex;
 use tracing::warn;
+use uuid::Uuid;
+
+use super::now_playing;
+use crate::data::{
+    guild_settings::GuildSettingsStore,
+    player_data::{prefetch, GuildPlayer, LoopMode, PlayerData, ResolverKind, TrackInfo},
+    stats::StatsStore,
+};
+
+/// How many times a track can fail to play before it's skipped.
+const MAX_PLAYBACK_FAILURES: u32 = 3;
+
+/// Tracks failing this early are started over instead of resumed.
+const MIN_RESUME_POSITION: Duration = Duration::from_secs(5);
 
-use crate::data::player_data::PlayerData;
+/// Follow the songs of a radio while it plays, refreshing its panel and
+/// recording them in the history.
+async fn follow_radio(
+    http: Arc<Http>,
+    guild_player: Arc<GuildPlayer>,
+    track_id: Uuid,
+    track_info: Arc<TrackInfo>,
+) {
+    let mut stream_titles = track_info.stream_title.subscribe();
+    let (station, url) = (track_info.get_title(), track_info.url.clone());
+    // the stream ends along with the track, closing the channel
+    drop(track_info);
+
+    let mut song = stream_titles.borrow_and_update().clone();
+    loop {
+        if let Some(song) = song {
+            guild_player
+                .record_history(format!("{} ({})", song, station), url.clone())
+                .await;
+        }
+
+        tokio::select! {
+            changed = stream_titles.changed() => if changed.is_err() { return },
+            _ = guild_player.cancel_token.cancelled() => return,
+        }
+        let still_playing = guild_player
+            .current()
+            .await
+            .is_some_and(|(handle, _)| handle.uuid() == track_id);
+        if !still_playing {
+            return;
+        }
+        song = stream_titles.borrow_and_update().clone();
+        now_playing::refresh_panel(&http, &guild_player).await;
+    }
+}
 
 #[derive(Debug)]
 pub struct PlayEventHandler {
     pub player_data: Arc<PlayerData>,
+    pub guild_player: Arc<GuildPlayer>,
+    pub guild_settings: Arc<GuildSettingsStore>,
+    pub stats: Arc<StatsStore>,
+    pub guild_id: GuildId,
     pub http: Arc<Http>,
 }
 
 #[async_trait]
 impl songbird::EventHandler for PlayEventHandler {
     async fn act(&self, ctx: &songbird::EventContext<'_>) -> Option<songbird::Event> {
-        // get the just started track's id
-        let track_id = {
+        // get the just started track
+        let (track_handle, fresh) = {
             let (track_state, track_handle) = match ctx {
                 songbird::EventContext::Track(track) => track,
                 _ => return None,
@@ -26,46 +85,109 @@ impl songbird::EventHandler for PlayEventHandler {
             if track_state.playing != PlayMode::Play {
                 return None;
             }
-            track_handle.uuid()
+            (
+                (*track_handle).clone(),
+                track_state.play_time == Duration::ZERO,
+            )
         };
 
-        // track ID -> guild ID
-        let guild_id = match self
-            .player_data
-            .track_2_guild
-            .lock()
+        // soundboard clips have no track info, and nothing to announce
+        let resume_at = self.guild_player.track_info(&track_handle).await?.resume_at;
+
+        // resumed after a pause, or played again after a failure, which
+        // keeps the panel of the failed track
+        let has_panel = self
+            .guild_player
+            .panel()
             .await
-            .get(&track_id)
-            .cloned()
-        {
-            Some(guild_id) => guild_id,
-            None => {
-                warn!("track_2_guild doesn't contain track_id");
-                return None;
+            .is_some_and(|panel| panel.track_id == track_handle.uuid());
+        if has_panel && !fresh {
+            now_playing::refresh_panel(&self.http, &self.guild_player).await;
+            return None;
+        }
+
+        // played again after a failure, pick up where it stopped
+        if let Some(resume_at) = resume_at {
+            let track_handle = track_handle.clone();
+            tokio::spawn(async move {
+                if let Err(e) = track_handle.seek_async(resume_at).await {
+                    warn!("can't resume the track where it failed: {}", e);
+                }
+            });
+        }
+
+        if self.guild_player.loop_mode().await == LoopMode::Track {
+            if let Err(e) = track_handle.enable_loop() {
+                warn!("can't loop the just started track: {}", e);
             }
-        };
+        }
 
-        // guild ID -> tracks[track ID] -> track info
-        let track_info = match self
-            .player_data
-            .guild_2_tracks
-            .lock()
-            .await
-            .get(&guild_id)
-            .and_then(|tracks| {
-                tracks
-                    .iter()
-                    .find(|track_info| track_info.id == track_id)
-                    .cloned()
-            }) {
-            Some(track_info) => track_info,
+        // the track info attached to it, and the ones after it
+        let tracks = self.guild_player.tracks().await;
+        let (position, track_info) = match tracks
+            .iter()
+            .position(|(handle, _)| handle.uuid() == track_handle.uuid())
+        {
+            Some(position) => (position, tracks[position].1.clone()),
             None => {
-                warn!("guild_2_tracks doesn't contain guild_id to get track_info");
+                warn!("the just started track isn't in the queue");
                 return None;
             }
         };
 
-        let channel_id: ChannelId = match track_info.text_channel_id {
+        // a track played again after failing, or held back for one, already
+        // counted as played, see `GuildPlayer::replay`
+        let first_play = self.guild_player.count_play(track_handle.uuid()).await;
+        if first_play && resume_at.is_none() {
+            self.guild_player
+                .record_history(track_info.get_title(), track_info.url.clone())
+                .await;
+            let started_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Time went backwards")
+                .as_secs();
+            self.stats
+                .record_play(
+                    self.guild_id,
+                    &track_info.url,
+                    &track_info.get_title(),
+                    track_info.requester.as_ref().map(|requester| requester.id),
+                    started_at,
+                )
+                .await;
+        }
+        if track_info.resolver == ResolverKind::Radio {
+            tokio::spawn(follow_radio(
+                self.http.clone(),
+                self.guild_player.clone(),
+                track_handle.uuid(),
+                track_info.clone(),
+            ));
+        }
+
+        // resolve the next tracks while this one plays
+        for (_, upcoming_track) in tracks
+            .iter()
+            .skip(position + 1)
+            .take(self.player_data.prefetch_count)
+        {
+            tokio::spawn(prefetch(
+                self.player_data.clone(),
+                self.guild_player.cancel_token.clone(),
+                upcoming_track.as_ref().clone(),
+            ));
+        }
+
+        if has_panel {
+            now_playing::refresh_panel(&self.http, &self.guild_player).await;
+            return None;
+        }
+
+        let guild_settings = self.guild_settings.get(self.guild_id).await;
+        let channel_id: ChannelId = match guild_settings
+            .announce_channel_id
+            .or(track_info.text_channel_id)
+        {
             Some(channel_id) => channel_id,
             None => {
                 warn!("track_info.text_channel_id is None");
@@ -73,72 +195,181 @@ impl songbird::EventHandler for PlayEventHandler {
             }
         };
 
-        // send msg to channel
-        if let Err(e) = channel_id
-            .send_message(
-                self.http.clone(),
-                CreateMessage::default().embed({
-                    let mut embed = CreateEmbed::default()
-                        .author(CreateEmbedAuthor::new("Now playing"))
-                        .title(track_info.get_title())
-                        .description(track_info.get_pretty_description())
-                        .url(&track_info.url);
-
-                    if let Some(thumbnail) = track_info.thumbnail.clone() {
-                        embed = embed.thumbnail(thumbnail);
-                    }
-                    embed
-                }),
-            )
-            .await
-        {
-            tracing::warn!("can't send message 'now playing': {}", e);
-        };
+        now_playing::send_panel(
+            &self.http,
+            &self.guild_player,
+            &guild_settings,
+            channel_id,
+            track_handle.uuid(),
+            &track_info,
+        )
+        .await;
 
         None
     }
 }
 
+/// Records how ended tracks were listened to, puts them back in the queue
+/// when looping it, and disables the panel once the queue runs out.
 #[derive(Debug)]
 pub struct EndEventHandler {
     pub player_data: Arc<PlayerData>,
+    pub guild_player: Arc<GuildPlayer>,
+    pub stats: Arc<StatsStore>,
+    pub guild_id: GuildId,
+    pub call: Arc<Mutex<Call>>,
+    pub http: Arc<Http>,
 }
 
 #[async_trait]
 impl songbird::EventHandler for EndEventHandler {
     async fn act(&self, ctx: &songbird::EventContext<'_>) -> Option<songbird::Event> {
-        // get the just ended track's id
-        let track_id = {
-            let track = match ctx {
-                songbird::EventContext::Track(track) => track,
-                _ => return None,
-            };
-            let (track_state, track_handle) = track[0];
-            match track_state.playing {
-                PlayMode::Pause | PlayMode::Play => return None,
-                _ => (),
+        let tracks = match ctx {
+            songbird::EventContext::Track(tracks) => tracks,
+            _ => return None,
+        };
+
+        let loop_queue = self.guild_player.loop_mode().await == LoopMode::Queue
+            && !self.guild_player.cancel_token.is_cancelled();
+        for (track_state, handle) in tracks.iter() {
+            // the error handler hands failed tracks' plays over to their retry
+            if !matches!(track_state.playing, PlayMode::Errored(_)) {
+                self.guild_player.forget_play(handle.uuid()).await;
+            }
+            let track_info = match self.guild_player.track_info(handle).await {
+                Some(track_info) => track_info,
+                None => continue,
             };
-            track_handle.uuid()
+
+            // tracks still waiting in the queue end too when it's stopped
+            if track_state.play_time > Duration::ZERO {
+                self.stats
+                    .record_end(
+                        self.guild_id,
+                        track_info.requester.as_ref().map(|requester| requester.id),
+                        track_state.play_time.as_secs(),
+                        track_state.position.as_secs(),
+                        track_info.duration_in_sec.filter(|_| !track_info.is_live()),
+                    )
+                    .await;
+            }
+
+            // failed tracks are played again or dropped by the error handler
+            if loop_queue && !matches!(track_state.playing, PlayMode::Errored(_)) {
+                let mut track_info = track_info.as_ref().clone();
+                track_info.id = Uuid::new_v4();
+                // a new pass, from the start
+                track_info.failures = 0;
+                track_info.resume_at = None;
+                self.guild_player
+                    .enqueue(self.player_data.clone(), &self.call, track_info)
+                    .await;
+            }
+        }
+
+        if self.guild_player.current().await.is_none() {
+            now_playing::close_panel(&self.http, &self.guild_player).await;
+        }
+
+        None
+    }
+}
+
+/// What went wrong with a track, without songbird's wrapping.
+fn describe_failure(play_error: &PlayError) -> String {
+    match play_error {
+        PlayError::Create(e) => match e.as_ref() {
+            AudioStreamError::Fail(e) => e.to_string(),
+            e => e.to_string(),
+        },
+        PlayError::Parse(e) | PlayError::Decode(e) | PlayError::Seek(e) => e.to_string(),
+        e => e.to_string(),
+    }
+}
+
+/// Tells the text channel when a track fails to play, and plays it again,
+/// with a fresh direct URL then downloaded, until it's failed too often.
+#[derive(Debug)]
+pub struct ErrorEventHandler {
+    pub player_data: Arc<PlayerData>,
+    pub guild_player: Arc<GuildPlayer>,
+    pub guild_settings: Arc<GuildSettingsStore>,
+    pub guild_id: GuildId,
+    pub call: Arc<Mutex<Call>>,
+    pub http: Arc<Http>,
+}
+
+#[async_trait]
+impl songbird::EventHandler for ErrorEventHandler {
+    async fn act(&self, ctx: &songbird::EventContext<'_>) -> Option<songbird::Event> {
+        let tracks = match ctx {
+            songbird::EventContext::Track(tracks) => tracks,
+            _ => return None,
         };
 
-        // get where the track ended from
-        let guild_id = *self.player_data.track_2_guild.lock().await.get(&track_id)?;
+        for (track_state, handle) in tracks.iter() {
+            let play_error = match &track_state.playing {
+                PlayMode::Errored(play_error) => play_error,
+                _ => continue,
+            };
+            // removed from the queue, or stopped by /nuke
+            let track_info = match self.guild_player.track_info(handle).await {
+                Some(track_info) if !self.guild_player.cancel_token.is_cancelled() => track_info,
+                _ => {
+                    self.guild_player.forget_play(handle.uuid()).await;
+                    continue;
+                }
+            };
+
+            let reason = describe_failure(play_error);
+            warn!("{} failed to play: {}", track_info.url, reason);
+
+            let mut track_info = track_info.as_ref().clone();
+            track_info.failures += 1;
+            let content = if track_info.failures < MAX_PLAYBACK_FAILURES {
+                format!(
+                    "⚠️ Couldn't play `{}`: {}\nTrying again...",
+                    track_info.get_title(),
+                    reason
+                )
+            } else {
+                format!(
+                    "⚠️ Couldn't play `{}` after `{}` tries, skipping it: {}",
+                    track_info.get_title(),
+                    track_info.failures,
+                    reason
+                )
+            };
 
-        // cleanup
-        let mut guild_2_tracks = self.player_data.guild_2_tracks.lock().await;
-        if let Some(tracks) = guild_2_tracks.get_mut(&guild_id) {
-            match tracks.len() {
-                0 | 1 => {
-                    guild_2_tracks.remove(&guild_id);
+            let guild_settings = self.guild_settings.get(self.guild_id).await;
+            match guild_settings
+                .announce_channel_id
+                .or(track_info.text_channel_id)
+            {
+                Some(channel_id) => {
+                    if let Err(e) = channel_id.say(&self.http, content).await {
+                        warn!("can't send message: {}", e);
+                    }
                 }
-                _ => tracks.retain(|track_info| track_info.id != track_id),
+                None => warn!("track_info.text_channel_id is None"),
+            }
+
+            if track_info.failures >= MAX_PLAYBACK_FAILURES {
+                self.guild_player.forget_play(handle.uuid()).await;
+                continue;
+            }
+            // the direct URL most likely went bad
+            track_info.stream_url.clear();
+            track_info.resume_at = match track_info.is_live() {
+                false if track_state.position >= MIN_RESUME_POSITION => Some(track_state.position),
+                _ => track_info.resume_at,
             };
-        };
-        self.player_data
-            .track_2_guild
-            .lock()
-            .await
-            .remove(&track_id);
+            let failed_id = handle.uuid();
+            track_info.id = Uuid::new_v4();
+            self.guild_player
+                .replay(self.player_data.clone(), &self.call, failed_id, track_info)
+                .await;
+        }
 
         None
     }
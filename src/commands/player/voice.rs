@@ -0,0 +1,156 @@
+use crate::data::player_data::PlayerData;
+
+use std::sync::Arc;
+
+use poise::serenity_prelude::{
+    Cache, ChannelId, ChannelType, Context as SerenityContext, EditVoiceState, GuildId, Member,
+    UserId,
+};
+use songbird::Call;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+/// The voice channel a user is in, according to the cache.
+pub fn voice_channel_of(cache: &Cache, guild_id: GuildId, user_id: UserId) -> Option<ChannelId> {
+    cache
+        .guild(guild_id)?
+        .voice_states
+        .get(&user_id)
+        .and_then(|voice_state| voice_state.channel_id)
+}
+
+/// The bot can be pulled out of its voice channel when nobody else is
+/// listening there, or by someone allowed to move members.
+pub fn ensure_can_move(cache: &Cache, guild_id: GuildId, member: &Member) -> Result<(), String> {
+    let bot_id = cache.current_user().id;
+    let guild = match cache.guild(guild_id) {
+        Some(guild) => guild,
+        None => return Err("Can't find this guild, try again later.".to_string()),
+    };
+    let bot_channel_id = match guild
+        .voice_states
+        .get(&bot_id)
+        .and_then(|voice_state| voice_state.channel_id)
+    {
+        Some(bot_channel_id) => bot_channel_id,
+        None => return Ok(()),
+    };
+
+    let someone_listening = guild.voice_states.values().any(|voice_state| {
+        voice_state.channel_id == Some(bot_channel_id)
+            && voice_state.user_id != bot_id
+            && voice_state.user_id != member.user.id
+            && !voice_state
+                .member
+                .as_ref()
+                .is_some_and(|member| member.user.bot)
+    });
+    let can_move_members = guild
+        .channels
+        .get(&bot_channel_id)
+        .is_some_and(|channel| guild.user_permissions_in(channel, member).move_members());
+
+    match someone_listening && !can_move_members {
+        true => Err(format!(
+            "Someone is still listening in <#{}>, join them instead!",
+            bot_channel_id
+        )),
+        false => Ok(()),
+    }
+}
+
+/// Whether a member may connect to a voice channel, so that they can't
+/// send the bot where they couldn't go themselves.
+pub fn ensure_can_connect(
+    cache: &Cache,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    member: &Member,
+) -> Result<(), String> {
+    let guild = match cache.guild(guild_id) {
+        Some(guild) => guild,
+        None => return Err("Can't find this guild, try again later.".to_string()),
+    };
+    match guild
+        .channels
+        .get(&channel_id)
+        .is_some_and(|channel| guild.user_permissions_in(channel, member).connect())
+    {
+        true => Ok(()),
+        false => Err(format!("You can't connect to <#{}>!", channel_id)),
+    }
+}
+
+/// Join or move to a voice channel, keeping the queue and the position of
+/// the playing track. On stage channels, the bot gets on stage if it can
+/// and requests to speak otherwise.
+pub async fn join(
+    ctx: &SerenityContext,
+    player_data: &PlayerData,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<Arc<Mutex<Call>>, String> {
+    let songbird_manager = songbird::get(ctx)
+        .await
+        .ok_or("Can't get Songbird manager!".to_string())?;
+    let call = songbird_manager
+        .join(guild_id, channel_id)
+        .await
+        .map_err(|e| format!("Can't join voice channel: {}", e))?;
+
+    // deafen the bot
+    {
+        let mut call = call.lock().await;
+        if !call.is_deaf() {
+            if let Err(e) = call.deafen(true).await {
+                warn!("can't deafen the bot: {}", e);
+            }
+        }
+    }
+
+    let stage_channel = ctx
+        .cache
+        .guild(guild_id)
+        .and_then(|guild| guild.channels.get(&channel_id).cloned())
+        .filter(|channel| channel.kind == ChannelType::Stage);
+    if let Some(stage_channel) = stage_channel {
+        if let Err(e) = stage_channel
+            .edit_own_voice_state(ctx, EditVoiceState::new().suppress(false))
+            .await
+        {
+            warn!("can't get on stage, requesting to speak: {}", e);
+            if let Err(e) = stage_channel
+                .edit_own_voice_state(ctx, EditVoiceState::new().request_to_speak(true))
+                .await
+            {
+                warn!("can't request to speak: {}", e);
+            }
+        }
+    }
+
+    if let Some(guild_player) = player_data.guild_player(guild_id).await {
+        // in case the bot wasn't connected when it last left
+        guild_player.take_leaving().await;
+        guild_player.resume_after_join().await;
+    }
+
+    Ok(call)
+}
+
+/// Get the call of a guild, joining `channel_id` if the bot isn't connected.
+pub async fn get_or_join(
+    ctx: &SerenityContext,
+    player_data: &PlayerData,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<Arc<Mutex<Call>>, String> {
+    let songbird_manager = songbird::get(ctx)
+        .await
+        .ok_or("Can't get Songbird manager!".to_string())?;
+    if let Some(call) = songbird_manager.get(guild_id) {
+        if call.lock().await.current_channel().is_some() {
+            return Ok(call);
+        }
+    }
+    join(ctx, player_data, guild_id, channel_id).await
+}
@@ -0,0 +1,137 @@
+use crate::{
+    data::guild_settings::{DuplicatePolicy, NowPlayingMode},
+    AppError, Context,
+};
+
+use poise::{
+    serenity_prelude::{ChannelType, GuildChannel},
+    ChoiceParameter,
+};
+
+/// Change how the bot behaves in this guild
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("now_playing", "announce_channel", "duplicates"),
+    subcommand_required
+)]
+pub async fn settings(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Post a "Now playing" message per track, or keep a single one
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "nowplaying"
+)]
+pub async fn now_playing(
+    ctx: Context<'_>,
+    #[description = "How \"Now playing\" messages are posted"] mode: NowPlayingMode,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    ctx.data()
+        .guild_settings
+        .update(guild_id, |settings| settings.now_playing_mode = mode)
+        .await;
+
+    if let Err(e) = ctx
+        .say(format!("\"Now playing\" messages: {}", mode.name()))
+        .await
+    {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Where "Now playing" messages go, leave empty for where /play was used
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "announce"
+)]
+pub async fn announce_channel(
+    ctx: Context<'_>,
+    #[description = "Channel to post \"Now playing\" messages in"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let channel_id = match channel {
+        Some(channel) if channel.kind != ChannelType::Text => {
+            if let Err(e) = ctx.say("That's not a text channel!").await {
+                tracing::warn!("can't send message: {}", e);
+            }
+            return Ok(());
+        }
+        Some(channel) => Some(channel.id),
+        None => None,
+    };
+
+    ctx.data()
+        .guild_settings
+        .update(guild_id, |settings| {
+            settings.announce_channel_id = channel_id
+        })
+        .await;
+
+    let content = match channel_id {
+        Some(channel_id) => format!(
+            "\"Now playing\" messages will be posted in <#{}>",
+            channel_id
+        ),
+        None => "\"Now playing\" messages will be posted where /play was used".to_string(),
+    };
+    if let Err(e) = ctx.say(content).await {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
+
+/// What to do with tracks added while they're already in the queue
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn duplicates(
+    ctx: Context<'_>,
+    #[description = "Tracks with the same source, or the same title and length"]
+    policy: DuplicatePolicy,
+) -> Result<(), AppError> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    ctx.data()
+        .guild_settings
+        .update(guild_id, |settings| settings.duplicate_policy = policy)
+        .await;
+
+    if let Err(e) = ctx
+        .say(format!("Duplicate tracks: {}", policy.name()))
+        .await
+    {
+        tracing::warn!("can't send message: {}", e);
+    }
+
+    Ok(())
+}
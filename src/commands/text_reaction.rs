@@ -101,14 +101,10 @@ impl TextReaction {
 
         let ref_msg =
             if let Some(ref_msg) = self.ref_msg_id.as_ref().and_then(|s| s.parse::<u64>().ok()) {
-                match ctx
-                    .channel_id()
+                ctx.channel_id()
                     .message(&ctx.serenity_context(), ref_msg)
                     .await
-                {
-                    Ok(ref_msg) => Some(ref_msg),
-                    Err(_) => None,
-                }
+                    .ok()
             } else {
                 None
             };
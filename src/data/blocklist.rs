@@ -0,0 +1,163 @@
+use super::player_data::{normalize_url, TrackInfo};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// What a blocklist entry is matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum BlockKind {
+    /// Part of the source URL, like a video, a channel or a whole site.
+    #[name = "URL"]
+    Url,
+    /// The uploader or artist, ignoring case.
+    #[name = "Uploader"]
+    Uploader,
+    /// A regex searched in the title, ignoring case.
+    #[name = "Title regex"]
+    Title,
+}
+
+/// Tracks a guild won't play, changed with /blocklist.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Blocklist {
+    pub urls: Vec<String>,
+    pub uploaders: Vec<String>,
+    pub titles: Vec<String>,
+}
+
+/// A URL as people type it: without scheme, `www.`, fragment, trailing
+/// slash or case.
+fn plain_url(url: &str) -> String {
+    let url = url.trim().to_lowercase();
+    let url = url.split('#').next().unwrap_or_default();
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    let url = url.strip_prefix("www.").unwrap_or(url);
+    url.trim_end_matches('/').to_string()
+}
+
+/// Compile a title regex of the blocklist.
+pub fn compile_title_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+impl Blocklist {
+    pub fn entries(&self, kind: BlockKind) -> &Vec<String> {
+        match kind {
+            BlockKind::Url => &self.urls,
+            BlockKind::Uploader => &self.uploaders,
+            BlockKind::Title => &self.titles,
+        }
+    }
+
+    pub fn entries_mut(&mut self, kind: BlockKind) -> &mut Vec<String> {
+        match kind {
+            BlockKind::Url => &mut self.urls,
+            BlockKind::Uploader => &mut self.uploaders,
+            BlockKind::Title => &mut self.titles,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.urls.is_empty() && self.uploaders.is_empty() && self.titles.is_empty()
+    }
+
+    /// Get the entries ready to check tracks against.
+    pub fn matcher(&self) -> BlocklistMatcher {
+        BlocklistMatcher {
+            urls: self
+                .urls
+                .iter()
+                .map(|url| UrlEntry {
+                    normalized: normalize_url(url),
+                    plain: plain_url(url),
+                    entry: url.clone(),
+                })
+                .collect(),
+            uploaders: self
+                .uploaders
+                .iter()
+                .map(|uploader| uploader.to_lowercase())
+                .collect(),
+            titles: self
+                .titles
+                .iter()
+                .filter_map(|pattern| match compile_title_pattern(pattern) {
+                    Ok(regex) => Some(regex),
+                    Err(e) => {
+                        warn!("invalid blocklist title regex {}: {}", pattern, e);
+                        None
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A URL entry of the blocklist, ready to match.
+#[derive(Debug)]
+struct UrlEntry {
+    /// Matches videos whatever the form of their URL.
+    normalized: String,
+    /// Matches sites and channels, whose URLs normalizing would lose.
+    plain: String,
+    entry: String,
+}
+
+/// A [`Blocklist`] with its entries normalized and compiled.
+#[derive(Debug, Default)]
+pub struct BlocklistMatcher {
+    urls: Vec<UrlEntry>,
+    uploaders: Vec<String>,
+    titles: Vec<Regex>,
+}
+
+impl BlocklistMatcher {
+    /// Why a track is blocked, if it is.
+    pub fn reason(&self, track_info: &TrackInfo) -> Option<String> {
+        // the track, and the channel it's from
+        let urls = [
+            Some(&track_info.url),
+            track_info.channel_url.as_ref(),
+            track_info.uploader_url.as_ref(),
+        ];
+        let mut normalized_urls = Vec::new();
+        let mut plain_urls = Vec::new();
+        for url in urls.into_iter().flatten() {
+            let normalized = normalize_url(url);
+            // youtu.be and such are still YouTube
+            if let Some(id) = normalized.strip_prefix("youtube:") {
+                plain_urls.push(format!("youtube.com/watch?v={}", id.to_lowercase()));
+            }
+            plain_urls.push(plain_url(url));
+            normalized_urls.push(normalized);
+        }
+        if let Some(url_entry) = self.urls.iter().find(|url_entry| {
+            normalized_urls
+                .iter()
+                .any(|url| url.contains(&url_entry.normalized))
+                || plain_urls.iter().any(|url| url.contains(&url_entry.plain))
+        }) {
+            return Some(format!("URL `{}` is blocked", url_entry.entry));
+        }
+
+        let uploaders = [&track_info.uploader, &track_info.artist];
+        if let Some(uploader) = uploaders
+            .into_iter()
+            .flatten()
+            .find(|uploader| self.uploaders.contains(&uploader.to_lowercase()))
+        {
+            return Some(format!("uploader `{}` is blocked", uploader));
+        }
+
+        let title = track_info.get_title();
+        self.titles
+            .iter()
+            .find(|regex| regex.is_match(&title))
+            .map(|regex| format!("title matches `{}`", regex.as_str()))
+    }
+}
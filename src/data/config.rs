@@ -1,11 +1,44 @@
+use std::time::Duration;
+
 /// Contains environment variables and other configurations.
 #[derive(Debug)]
 pub struct Config {
     pub yt_dlp_path: String,
     pub ffmpeg_path: String,
+    /// How many `yt-dlp` processes can run at once, across all guilds. Imports
+    /// and getting tracks ready to play each get this many.
+    pub yt_dlp_max_concurrency: usize,
+    pub yt_dlp_resolve_timeout: Duration,
+    pub yt_dlp_download_timeout: Duration,
 
     pub discord_token: String,
     pub bot_maintainer_uid: String,
+
+    /// Where downloaded tracks are cached, shared by all guilds.
+    pub cache_dir: String,
+    /// Size limit of the track cache, in bytes.
+    pub cache_max_size: u64,
+    /// How many upcoming tracks get their stream resolved in advance.
+    pub prefetch_count: usize,
+
+    /// Pick opus formats first, Discord gets them without re-encoding.
+    pub format_prefer_opus: bool,
+    /// Formats above this audio bitrate, in kbps, are avoided.
+    pub format_max_bitrate: Option<f32>,
+    /// Protocols formats can be streamed over.
+    pub format_protocols: Vec<String>,
+
+    /// Where guild settings and other persistent state are kept.
+    pub data_dir: String,
+    /// Local music library, played with `local:` queries.
+    pub music_dir: Option<String>,
+    /// JSON file of title cleanup rules, replacing the built-in ones.
+    pub title_rules_path: Option<String>,
+    /// Soundboard clips shared by every guild.
+    pub sfx_dir: Option<String>,
+    /// Make up tracks for `fake://` queries, to try the player out. Each
+    /// one is held in memory while it plays.
+    pub fake_tracks: bool,
 }
 
 impl Config {
@@ -13,6 +46,23 @@ impl Config {
         std::env::var(key).unwrap_or_else(|_| panic!("{} must be set.", key))
     }
 
+    fn get_env_or(key: &str, default: &str) -> String {
+        std::env::var(key)
+            .ok()
+            .filter(|value| !value.is_empty())
+            .unwrap_or_else(|| default.to_string())
+    }
+
+    fn get_env_parsed_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+        match std::env::var(key).ok().filter(|value| !value.is_empty()) {
+            Some(value) => value.parse().unwrap_or_else(|_| {
+                tracing::error!("{} is not a valid value", key);
+                std::process::exit(1);
+            }),
+            None => default,
+        }
+    }
+
     pub fn init() -> Self {
         Self {
             yt_dlp_path: {
@@ -27,6 +77,15 @@ impl Config {
                 }
                 path
             },
+            yt_dlp_max_concurrency: Self::get_env_parsed_or("YT_DLP_MAX_CONCURRENCY", 4).max(1),
+            yt_dlp_resolve_timeout: Duration::from_secs(Self::get_env_parsed_or(
+                "YT_DLP_RESOLVE_TIMEOUT_SECS",
+                60,
+            )),
+            yt_dlp_download_timeout: Duration::from_secs(Self::get_env_parsed_or(
+                "YT_DLP_DOWNLOAD_TIMEOUT_SECS",
+                600,
+            )),
             ffmpeg_path: {
                 let path = Self::get_env("FFMPEG_PATH");
                 if path.is_empty() {
@@ -55,6 +114,58 @@ impl Config {
                 }
                 path
             },
+            cache_dir: Self::get_env_or("CACHE_DIR", "/tmp/taxer/cache"),
+            cache_max_size: Self::get_env_parsed_or("CACHE_MAX_SIZE_MB", 2048) * 1024 * 1024,
+            prefetch_count: Self::get_env_parsed_or("PREFETCH_COUNT", 2),
+            format_prefer_opus: Self::get_env_parsed_or("FORMAT_PREFER_OPUS", true),
+            format_max_bitrate: match Self::get_env_parsed_or("FORMAT_MAX_BITRATE_KBPS", 0.0) {
+                max_bitrate if max_bitrate > 0.0 => Some(max_bitrate),
+                _ => None,
+            },
+            format_protocols: Self::get_env_or("FORMAT_PROTOCOLS", "https,http")
+                .split(',')
+                .map(|protocol| protocol.trim().to_lowercase())
+                .filter(|protocol| !protocol.is_empty())
+                .collect(),
+            data_dir: Self::get_env_or("DATA_DIR", "data"),
+            music_dir: std::env::var("MUSIC_DIR")
+                .ok()
+                .filter(|value| !value.is_empty()),
+            title_rules_path: std::env::var("TITLE_RULES_PATH")
+                .ok()
+                .filter(|value| !value.is_empty()),
+            sfx_dir: std::env::var("SFX_DIR")
+                .ok()
+                .filter(|value| !value.is_empty()),
+            fake_tracks: Self::get_env_parsed_or("FAKE_TRACKS", false),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// A config for tests, keeping everything under `dir` and making up
+    /// tracks instead of running `yt-dlp`.
+    pub fn for_tests(dir: &std::path::Path) -> Self {
+        Self {
+            yt_dlp_path: "yt-dlp".to_string(),
+            ffmpeg_path: "ffmpeg".to_string(),
+            yt_dlp_max_concurrency: 1,
+            yt_dlp_resolve_timeout: Duration::from_secs(30),
+            yt_dlp_download_timeout: Duration::from_secs(60),
+            discord_token: String::new(),
+            bot_maintainer_uid: String::new(),
+            cache_dir: dir.join("cache").display().to_string(),
+            cache_max_size: 64 * 1024 * 1024,
+            prefetch_count: 0,
+            format_prefer_opus: true,
+            format_max_bitrate: None,
+            format_protocols: vec!["https".to_string(), "http".to_string()],
+            data_dir: dir.join("data").display().to_string(),
+            music_dir: None,
+            title_rules_path: None,
+            sfx_dir: None,
+            fake_tracks: true,
         }
     }
 }
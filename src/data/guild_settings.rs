@@ -0,0 +1,103 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use super::{blocklist::Blocklist, title_rules::TitleRule};
+
+use poise::serenity_prelude::{ChannelId, GuildId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::warn;
+
+/// How "Now playing" messages are posted.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub enum NowPlayingMode {
+    /// A new message for every track, older ones lose their buttons.
+    #[default]
+    #[name = "One message per track"]
+    PerTrack,
+    /// A single message, edited in place while it's the channel's latest,
+    /// re-posted at the bottom otherwise.
+    #[name = "A single message"]
+    Single,
+}
+
+/// What happens to tracks already in the queue when they're added again.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub enum DuplicatePolicy {
+    #[name = "Add them"]
+    Allow,
+    /// Add them, telling how many there were.
+    #[default]
+    #[name = "Add them with a warning"]
+    Warn,
+    #[name = "Skip them"]
+    Reject,
+}
+
+/// Settings of a guild, changed with /settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildSettings {
+    pub now_playing_mode: NowPlayingMode,
+    /// Where "Now playing" messages go, instead of where /play was used.
+    pub announce_channel_id: Option<ChannelId>,
+    pub duplicate_policy: DuplicatePolicy,
+    pub blocklist: Blocklist,
+    /// Saved radio stations, URL by name.
+    pub radio_stations: BTreeMap<String, String>,
+    /// Title cleanup rules replacing the global ones.
+    pub title_rules: Option<Vec<TitleRule>>,
+}
+
+/// The settings of every guild, kept in a JSON file.
+#[derive(Debug)]
+pub struct GuildSettingsStore {
+    path: PathBuf,
+    settings: RwLock<HashMap<GuildId, GuildSettings>>,
+}
+
+impl GuildSettingsStore {
+    pub fn new(data_dir: &str) -> Self {
+        let path = Path::new(data_dir).join("guild_settings.json");
+        let settings = super::load_json(&path);
+
+        Self {
+            path,
+            settings: RwLock::new(settings),
+        }
+    }
+
+    /// Get the settings of a guild, the defaults if it never changed them.
+    pub async fn get(&self, guild_id: GuildId) -> GuildSettings {
+        self.settings
+            .read()
+            .await
+            .get(&guild_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Change the settings of a guild and save them, returns the new ones.
+    pub async fn update(
+        &self,
+        guild_id: GuildId,
+        change: impl FnOnce(&mut GuildSettings),
+    ) -> GuildSettings {
+        let mut settings = self.settings.write().await;
+        let guild_settings = settings.entry(guild_id).or_default();
+        change(guild_settings);
+        let guild_settings = guild_settings.clone();
+
+        // the lock is held while writing so that saves never interleave
+        if let Err(e) = super::save_json(&self.path, &*settings).await {
+            warn!("can't save guild settings: {}", e);
+        }
+        guild_settings
+    }
+}
@@ -1,26 +1,54 @@
+pub mod blocklist;
 pub mod config;
+pub mod guild_settings;
 pub mod player_data;
+pub mod schedules;
+pub mod sfx;
+pub mod stats;
+pub mod title_rules;
 
 use config::Config;
+use guild_settings::GuildSettingsStore;
 use player_data::PlayerData;
+use schedules::ScheduleStore;
+use sfx::SfxStore;
+use stats::StatsStore;
+use title_rules::TitleRules;
 
+use std::path::Path;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use poise::serenity_prelude::ShardManager;
+use poise::serenity_prelude::{GuildId, ShardManager};
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{error, warn};
 pub struct Data {
     pub config: Config,
     pub player_data: Arc<PlayerData>,
+    pub guild_settings: Arc<GuildSettingsStore>,
+    /// Title cleanup rules of guilds that don't have their own.
+    pub title_rules: Arc<TitleRules>,
+    /// Soundboard clips.
+    pub sfx: SfxStore,
+    /// Playback scheduled with /schedule.
+    pub schedules: Arc<ScheduleStore>,
+    /// Listening stats, for /stats.
+    pub stats: Arc<StatsStore>,
     pub shard_manager: Arc<ShardManager>,
     pub start_time: u64,
 }
 
 impl Data {
     /// Create a new [`Data`] instance.
-    pub fn new(config: Config, shard_manager: Arc<ShardManager>) -> Self {
+    pub async fn new(config: Config, shard_manager: Arc<ShardManager>) -> Self {
         Self {
+            player_data: Arc::new(PlayerData::new(&config).await),
+            guild_settings: Arc::new(GuildSettingsStore::new(&config.data_dir)),
+            title_rules: Arc::new(TitleRules::load(config.title_rules_path.as_deref())),
+            sfx: SfxStore::new(config.sfx_dir.as_deref(), &config.data_dir),
+            schedules: Arc::new(ScheduleStore::new(&config.data_dir)),
+            stats: Arc::new(StatsStore::new(&config.data_dir)),
             config,
-            player_data: Arc::new(PlayerData::default()),
             shard_manager,
             start_time: SystemTime::now()
                 .duration_since(UNIX_EPOCH)
@@ -28,4 +56,92 @@ impl Data {
                 .as_secs(),
         }
     }
+
+    /// The title cleanup rules of a guild, its own or the global ones.
+    pub async fn title_rules_of(&self, guild_id: GuildId) -> TitleRules {
+        let guild_settings = self.guild_settings.get(guild_id).await;
+        self.title_rules.of_guild(guild_id, &guild_settings)
+    }
+}
+
+/// Read a JSON file of the data directory, starting over when it's missing
+/// or broken. A broken file is moved aside so the next save doesn't wipe it.
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> T {
+    match std::fs::read(path) {
+        Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+            error!("can't parse {}, starting over: {}", path.display(), e);
+            move_aside(path);
+            T::default()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => T::default(),
+        Err(e) => {
+            error!("can't read {}, starting over: {}", path.display(), e);
+            T::default()
+        }
+    }
+}
+
+/// Rename a broken file to `<name>.broken-<unix time>`.
+fn move_aside(path: &Path) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let mut broken_path = path.as_os_str().to_owned();
+    broken_path.push(format!(".broken-{}", timestamp));
+    match std::fs::rename(path, &broken_path) {
+        Ok(_) => warn!("moved broken {} to {:?}", path.display(), broken_path),
+        Err(e) => error!("can't move broken {} aside: {}", path.display(), e),
+    }
+}
+
+/// Write a JSON file of the data directory, creating the directory if
+/// needed.
+pub async fn save_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), String> {
+    let content = serde_json::to_vec_pretty(value)
+        .map_err(|e| format!("can't serialize {}: {}", path.display(), e))?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("can't create {}: {}", parent.display(), e))?;
+    }
+
+    // write then rename, a crash never leaves a half-written file
+    let tmp_path = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp_path, content)
+        .await
+        .map_err(|e| format!("can't write {}: {}", tmp_path.display(), e))?;
+    tokio::fs::rename(&tmp_path, path)
+        .await
+        .map_err(|e| format!("can't rename {}: {}", tmp_path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn broken_files_are_kept() {
+        let dir = std::env::temp_dir().join(format!("taxer-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stats.json");
+        std::fs::write(&path, "{ not json").unwrap();
+
+        let loaded: HashMap<String, u64> = load_json(&path);
+        assert!(loaded.is_empty());
+        assert!(!path.exists());
+        let kept = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(kept.len(), 1);
+        assert!(kept[0].starts_with("stats.json.broken-"));
+        assert_eq!(
+            std::fs::read_to_string(dir.join(&kept[0])).unwrap(),
+            "{ not json"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
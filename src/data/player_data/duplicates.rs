@@ -0,0 +1,134 @@
+use super::TrackInfo;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
+
+use regex::Regex;
+
+/// Tracks whose durations are this close are the same, when titles match.
+const DURATION_TOLERANCE_IN_SEC: u64 = 2;
+
+/// The video ID of YouTube URLs, whatever their form.
+static YOUTUBE_ID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?:https?://)?(?:(?:www|m|music)\.)?(?:youtube\.com/(?:watch\?(?:.*&)?v=|shorts/|live/|embed/)|youtu\.be/)([\w-]{11})",
+    )
+    .expect("the YouTube URL regex is valid")
+});
+
+/// The same track can come from different URLs: YouTube ones through their
+/// video ID, others without their scheme, `www.`, fragment or trailing slash.
+pub fn normalize_url(url: &str) -> String {
+    if let Some(captures) = YOUTUBE_ID.captures(url) {
+        return format!("youtube:{}", &captures[1]);
+    }
+
+    let url = url.split('#').next().unwrap_or_default();
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    let url = url.strip_prefix("www.").unwrap_or(url);
+    let (host, path) = url.split_once('/').unwrap_or((url, ""));
+    format!("{}/{}", host.to_lowercase(), path.trim_end_matches('/'))
+}
+
+/// Titles compared without case, punctuation or spacing.
+fn normalize_title(title: &str) -> String {
+    title
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Tracks already queued, to tell whether new ones are duplicates: same
+/// source, or same cleaned title and duration.
+#[derive(Debug, Default)]
+pub struct SeenTracks {
+    urls: HashSet<String>,
+    durations_by_title: HashMap<String, Vec<u64>>,
+}
+
+impl SeenTracks {
+    pub fn new<'a>(tracks: impl IntoIterator<Item = &'a TrackInfo>) -> Self {
+        let mut seen_tracks = Self::default();
+        for track_info in tracks {
+            seen_tracks.insert(track_info);
+        }
+        seen_tracks
+    }
+
+    pub fn contains(&self, track_info: &TrackInfo) -> bool {
+        if self.urls.contains(&normalize_url(&track_info.url)) {
+            return true;
+        }
+        // livestreams with the same title aren't necessarily the same
+        let title = normalize_title(&track_info.get_title());
+        match track_info.duration_in_sec {
+            Some(duration_in_sec) if !track_info.is_live() && !title.is_empty() => self
+                .durations_by_title
+                .get(&title)
+                .is_some_and(|durations| {
+                    durations
+                        .iter()
+                        .any(|seen| seen.abs_diff(duration_in_sec) <= DURATION_TOLERANCE_IN_SEC)
+                }),
+            _ => false,
+        }
+    }
+
+    pub fn insert(&mut self, track_info: &TrackInfo) {
+        self.urls.insert(normalize_url(&track_info.url));
+        if let Some(duration_in_sec) = track_info.duration_in_sec.filter(|_| !track_info.is_live())
+        {
+            self.durations_by_title
+                .entry(normalize_title(&track_info.get_title()))
+                .or_default()
+                .push(duration_in_sec);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn youtube_urls_become_video_ids() {
+        for url in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://youtube.com/watch?feature=share&v=dQw4w9WgXcQ&t=10",
+            "https://m.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ&list=RD",
+            "https://youtu.be/dQw4w9WgXcQ?si=abc",
+            "https://www.youtube.com/shorts/dQw4w9WgXcQ",
+            "youtube.com/embed/dQw4w9WgXcQ",
+        ] {
+            assert_eq!(normalize_url(url), "youtube:dQw4w9WgXcQ", "{}", url);
+        }
+    }
+
+    #[test]
+    fn other_urls_lose_what_doesnt_matter() {
+        assert_eq!(
+            normalize_url("https://www.SoundCloud.com/artist/track/#comments"),
+            "soundcloud.com/artist/track"
+        );
+        assert_eq!(
+            normalize_url("http://example.com/Song.mp3"),
+            "example.com/Song.mp3"
+        );
+        assert_eq!(normalize_url("https://example.com"), "example.com/");
+    }
+
+    #[test]
+    fn channels_aren_t_videos() {
+        assert_eq!(
+            normalize_url("https://www.youtube.com/@channel"),
+            "youtube.com/@channel"
+        );
+    }
+}
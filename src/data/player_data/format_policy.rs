@@ -0,0 +1,245 @@
+use super::track_info::Format;
+use crate::data::config::Config;
+
+use tracing::{debug, info};
+
+/// Containers that songbird and our symphonia features can read.
+const DECODABLE_CONTAINERS: [&str; 10] = [
+    "webm", "mkv", "mka", "ogg", "oga", "opus", "mp3", "flac", "wav", "aac",
+];
+
+/// Audio codecs that songbird and our symphonia features can decode.
+const DECODABLE_CODECS: [&str; 7] = ["opus", "vorbis", "aac", "mp3", "flac", "alac", "pcm"];
+
+/// Codecs by how good they are at the same bitrate.
+fn codec_rank(codec: &str) -> u8 {
+    match codec {
+        "flac" | "alac" | "pcm" => 3,
+        "opus" => 2,
+        "aac" | "vorbis" => 1,
+        _ => 0,
+    }
+}
+
+/// yt-dlp names codecs with their profile, like `mp4a.40.2` or `opus`.
+fn normalize_codec(codec: &str) -> &str {
+    match codec {
+        codec if codec.starts_with("mp4a") => "aac",
+        codec if codec.starts_with("pcm") => "pcm",
+        codec => codec,
+    }
+}
+
+/// How a format is picked among the ones `yt-dlp` offers, set from the
+/// `FORMAT_*` environment variables.
+#[derive(Debug)]
+pub struct FormatPolicy {
+    /// Opus goes to Discord without being re-encoded, when nothing else
+    /// is mixed in.
+    prefer_opus: bool,
+    /// Formats above this are only used when there's nothing else, in kbps.
+    max_bitrate: Option<f32>,
+    /// Protocols that can be streamed as a plain file, like `https`.
+    allowed_protocols: Vec<String>,
+}
+
+impl FormatPolicy {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            prefer_opus: config.format_prefer_opus,
+            max_bitrate: config.format_max_bitrate,
+            allowed_protocols: config.format_protocols.clone(),
+        }
+    }
+
+    /// Why a format can't be streamed, if it can't.
+    fn rejection(&self, format: &Format) -> Option<String> {
+        let protocol = format.protocol.as_deref().unwrap_or("https");
+        if !self
+            .allowed_protocols
+            .iter()
+            .any(|allowed| allowed == protocol)
+        {
+            return Some(format!("protocol {} isn't allowed", protocol));
+        }
+        let codec = match format.codec.as_deref().map(normalize_codec) {
+            None | Some("none") => return Some("no audio".to_string()),
+            Some(codec) if !DECODABLE_CODECS.contains(&codec) => {
+                return Some(format!("codec {} can't be decoded", codec))
+            }
+            Some(codec) => codec,
+        };
+        let container = format.container_name();
+        if !DECODABLE_CONTAINERS.contains(&container.as_str()) {
+            return Some(format!(
+                "{} in {} can't be demuxed",
+                codec,
+                match container.is_empty() {
+                    true => "an unknown container",
+                    false => &container,
+                }
+            ));
+        }
+        None
+    }
+
+    /// Pick the format to stream a track from, logging why. `None` when
+    /// none of them can be streamed.
+    pub fn pick<'a>(
+        &self,
+        formats: &'a [Format],
+        duration_in_sec: Option<u64>,
+        track_url: &str,
+    ) -> Option<&'a Format> {
+        let candidates = formats
+            .iter()
+            .filter(|format| match self.rejection(format) {
+                Some(reason) => {
+                    debug!(
+                        "{}: skipped format {}: {}",
+                        track_url,
+                        format.name(),
+                        reason
+                    );
+                    false
+                }
+                None => true,
+            })
+            .collect::<Vec<_>>();
+
+        let bitrate_of = |format: &Format| format.estimated_bitrate(duration_in_sec);
+        let within_cap = |format: &&&Format| match (self.max_bitrate, bitrate_of(format)) {
+            (Some(max_bitrate), Some(bitrate)) => bitrate <= max_bitrate,
+            _ => true,
+        };
+        let codec_of = |format: &Format| {
+            format
+                .codec
+                .as_deref()
+                .map(normalize_codec)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let opus_first = |format: &Format| self.prefer_opus && codec_of(format) == "opus";
+        let best = |formats: &mut dyn Iterator<Item = &&'a Format>| {
+            formats
+                .max_by(|a, b| {
+                    // muxed formats would download the video for nothing
+                    a.is_audio_only()
+                        .cmp(&b.is_audio_only())
+                        .then(opus_first(a).cmp(&opus_first(b)))
+                        .then(codec_rank(&codec_of(a)).cmp(&codec_rank(&codec_of(b))))
+                        .then(
+                            bitrate_of(a)
+                                .unwrap_or(0.0)
+                                .total_cmp(&bitrate_of(b).unwrap_or(0.0)),
+                        )
+                })
+                .copied()
+        };
+
+        let (picked, reason) = match best(&mut candidates.iter().filter(within_cap)) {
+            Some(format) if opus_first(format) => (format, "opus is preferred for passthrough"),
+            Some(format) => (format, "best codec and bitrate"),
+            // everything is above the cap, go as low as possible
+            None => (
+                candidates.iter().copied().min_by(|a, b| {
+                    bitrate_of(a)
+                        .unwrap_or(f32::MAX)
+                        .total_cmp(&bitrate_of(b).unwrap_or(f32::MAX))
+                })?,
+                "lowest bitrate, all of them are above the cap",
+            ),
+        };
+        info!("{}: picked format {}: {}", track_url, picked.name(), reason);
+        Some(picked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(format_id: &str, codec: &str, ext: &str, bitrate: f32) -> Format {
+        serde_json::from_value(serde_json::json!({
+            "url": format!("https://example.com/{}", format_id),
+            "format_id": format_id,
+            "acodec": codec,
+            "vcodec": "none",
+            "abr": bitrate,
+            "ext": ext,
+            "protocol": "https",
+        }))
+        .unwrap()
+    }
+
+    fn policy(prefer_opus: bool, max_bitrate: Option<f32>) -> FormatPolicy {
+        FormatPolicy {
+            prefer_opus,
+            max_bitrate,
+            allowed_protocols: vec!["https".to_string()],
+        }
+    }
+
+    fn pick(policy: &FormatPolicy, formats: &[Format]) -> Option<String> {
+        policy
+            .pick(formats, Some(180), "test")
+            .and_then(|format| format.format_id.clone())
+    }
+
+    #[test]
+    fn prefers_opus() {
+        let formats = [
+            format("aac", "mp4a.40.2", "m4a", 128.0),
+            format("opus", "opus", "webm", 96.0),
+        ];
+        assert_eq!(pick(&policy(true, None), &formats).as_deref(), Some("opus"));
+    }
+
+    #[test]
+    fn otherwise_picks_the_best_codec_then_bitrate() {
+        let formats = [
+            format("mp3", "mp3", "mp3", 320.0),
+            format("aac-low", "mp4a.40.5", "aac", 48.0),
+            format("aac-high", "mp4a.40.2", "aac", 160.0),
+        ];
+        assert_eq!(
+            pick(&policy(false, None), &formats).as_deref(),
+            Some("aac-high")
+        );
+    }
+
+    #[test]
+    fn stays_under_the_cap() {
+        let formats = [
+            format("opus-high", "opus", "webm", 160.0),
+            format("opus-low", "opus", "webm", 64.0),
+        ];
+        assert_eq!(
+            pick(&policy(true, Some(100.0)), &formats).as_deref(),
+            Some("opus-low")
+        );
+        // the lowest one when they're all above it
+        assert_eq!(
+            pick(&policy(true, Some(32.0)), &formats).as_deref(),
+            Some("opus-low")
+        );
+    }
+
+    #[test]
+    fn skips_what_can_t_be_streamed() {
+        let mut hls = format("hls", "opus", "webm", 160.0);
+        hls.protocol = Some("m3u8_native".to_string());
+        let mut video = format("video", "opus", "webm", 160.0);
+        video.video_codec = Some("vp9".to_string());
+        let formats = [
+            hls,
+            format("ac3", "ac-3", "mp4", 320.0),
+            format("mp4", "opus", "mp4", 128.0),
+            video,
+            format("ok", "opus", "webm", 48.0),
+        ];
+        assert_eq!(pick(&policy(true, None), &formats).as_deref(), Some("ok"));
+        assert_eq!(pick(&policy(true, None), &formats[..3]), None);
+    }
+}
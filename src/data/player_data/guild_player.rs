@@ -0,0 +1,615 @@
+use super::{LazyTrack, PlayerData, TrackInfo};
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex as StdMutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use poise::serenity_prelude::{ChannelId, MessageId, UserId};
+use rand::seq::SliceRandom;
+use songbird::{
+    input::Input,
+    tracks::{PlayMode, Track, TrackHandle, TrackQueue, TrackResult},
+    typemap::TypeMapKey,
+    Call,
+};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Attaches a [`TrackInfo`] to songbird's [`TrackHandle`].
+pub struct TrackInfoKey;
+
+impl TypeMapKey for TrackInfoKey {
+    type Value = Arc<TrackInfo>;
+}
+
+/// What to do once a track ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopMode {
+    #[default]
+    Off,
+    /// Play the same track again.
+    Track,
+    /// Put the track back at the end of the queue.
+    Queue,
+}
+
+impl LoopMode {
+    /// The mode after this one, for toggling through them.
+    pub fn next(self) -> Self {
+        match self {
+            LoopMode::Off => LoopMode::Track,
+            LoopMode::Track => LoopMode::Queue,
+            LoopMode::Queue => LoopMode::Off,
+        }
+    }
+}
+
+/// The "Now playing" message of a track, with its control buttons.
+#[derive(Debug, Clone, Copy)]
+pub struct NowPlayingPanel {
+    pub track_id: Uuid,
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+}
+
+/// A track or radio song that played in a guild.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub title: String,
+    pub url: String,
+    /// Unix timestamp, in seconds.
+    pub played_at: u64,
+}
+
+/// A running /sleep timer.
+#[derive(Debug)]
+struct SleepTimer {
+    id: Uuid,
+    /// Unix timestamp, in seconds.
+    ends_at: u64,
+    cancel_token: CancellationToken,
+}
+
+/// How many entries a guild's play history keeps.
+const MAX_HISTORY_LEN: usize = 100;
+
+/// Playback settings of a guild, and the panel controlling them.
+#[derive(Debug)]
+struct PlayerState {
+    loop_mode: LoopMode,
+    volume_percent: u32,
+    panel: Option<NowPlayingPanel>,
+    /// The playing track got paused by /leave.
+    paused_by_leave: bool,
+    /// The bot is leaving its voice channel on purpose, it's not being
+    /// disconnected by someone.
+    leaving: bool,
+    /// What played, the latest last.
+    history: VecDeque<HistoryEntry>,
+    sleep_timer: Option<SleepTimer>,
+    /// Tracks whose play got recorded, so that starting again after being
+    /// paused or held back doesn't record it twice.
+    counted_plays: HashSet<Uuid>,
+}
+
+/// The player of a single guild. Guilds never contend on each other's
+/// locks, and songbird's queue is the only source of truth for the tracks.
+#[derive(Debug)]
+pub struct GuildPlayer {
+    /// songbird's queue of the guild's call, shared with it.
+    queue: TrackQueue,
+
+    /// Held while adding tracks, so that event handlers never see a track
+    /// handle before its [`TrackInfo`] is attached to it.
+    queue_lock: Mutex<()>,
+
+    state: Mutex<PlayerState>,
+
+    /// Cancelled by /nuke, stops every import and track resolution
+    /// running for the guild.
+    pub cancel_token: CancellationToken,
+
+    /// Running /play imports by ID, with who started them. A std mutex so
+    /// that [`ImportGuard`] can unregister its import on drop.
+    imports: StdMutex<HashMap<Uuid, (UserId, CancellationToken)>>,
+}
+
+/// Keeps a /play import registered to its guild until dropped.
+pub struct ImportGuard<'a> {
+    guild_player: &'a GuildPlayer,
+    pub id: Uuid,
+    /// Cancelled by /cancel, the import's "Cancel" button, or /nuke.
+    pub cancel_token: CancellationToken,
+}
+
+impl Drop for ImportGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut imports) = self.guild_player.imports.lock() {
+            imports.remove(&self.id);
+        }
+    }
+}
+
+/// How far the volume can be turned up, in percent.
+pub const MAX_VOLUME_PERCENT: u32 = 200;
+
+impl GuildPlayer {
+    pub fn new(queue: TrackQueue) -> Self {
+        Self {
+            queue,
+            queue_lock: Mutex::new(()),
+            state: Mutex::new(PlayerState {
+                loop_mode: LoopMode::Off,
+                volume_percent: 100,
+                panel: None,
+                paused_by_leave: false,
+                leaving: false,
+                history: VecDeque::new(),
+                sleep_timer: None,
+                counted_plays: HashSet::new(),
+            }),
+            cancel_token: CancellationToken::new(),
+            imports: StdMutex::new(HashMap::new()),
+        }
+    }
+
+    /// Register a new import started by `user_id`.
+    pub fn start_import(&self, user_id: UserId) -> ImportGuard<'_> {
+        let id = Uuid::new_v4();
+        let cancel_token = self.cancel_token.child_token();
+        if let Ok(mut imports) = self.imports.lock() {
+            imports.insert(id, (user_id, cancel_token.clone()));
+        }
+        ImportGuard {
+            guild_player: self,
+            id,
+            cancel_token,
+        }
+    }
+
+    /// Cancel every import started by `user_id`, returns how many there were.
+    pub fn cancel_imports_of(&self, user_id: UserId) -> usize {
+        let imports = match self.imports.lock() {
+            Ok(imports) => imports,
+            Err(_) => return 0,
+        };
+        imports
+            .values()
+            .filter(|(owner_id, _)| *owner_id == user_id)
+            .map(|(_, cancel_token)| cancel_token.cancel())
+            .count()
+    }
+
+    /// Cancel every running import of the guild.
+    pub fn cancel_imports(&self) {
+        if let Ok(imports) = self.imports.lock() {
+            imports
+                .values()
+                .for_each(|(_, cancel_token)| cancel_token.cancel());
+        }
+    }
+
+    /// A track whose stream is resolved right before it plays.
+    async fn lazy_track(&self, player_data: Arc<PlayerData>, track_info: &TrackInfo) -> Track {
+        let volume = self.volume_percent().await as f32 / 100.0;
+        Track::new_with_uuid(
+            Input::Lazy(Box::new(LazyTrack::new(
+                player_data,
+                self.cancel_token.clone(),
+                track_info.clone(),
+            ))),
+            track_info.id,
+        )
+        .volume(volume)
+    }
+
+    /// Add a track to the end of the queue, its stream is resolved right
+    /// before it plays.
+    pub async fn enqueue(
+        &self,
+        player_data: Arc<PlayerData>,
+        call: &Mutex<Call>,
+        track_info: TrackInfo,
+    ) {
+        let track = self.lazy_track(player_data, &track_info).await;
+
+        let _queue_lock = self.queue_lock.lock().await;
+        let handle = call.lock().await.enqueue(track).await;
+        handle
+            .typemap()
+            .write()
+            .await
+            .insert::<TrackInfoKey>(Arc::new(track_info));
+    }
+
+    /// Play a track again in place of `failed_id`, a track that failed.
+    /// Whether songbird already moved on from the failed track or not, the
+    /// new one plays before anything that was after it.
+    pub async fn replay(
+        &self,
+        player_data: Arc<PlayerData>,
+        call: &Mutex<Call>,
+        failed_id: Uuid,
+        track_info: TrackInfo,
+    ) {
+        // before it can start, so that it's never announced nor counted anew
+        self.hand_over(failed_id, track_info.id).await;
+        let track = self.lazy_track(player_data, &track_info).await;
+
+        let _queue_lock = self.queue_lock.lock().await;
+        let handle = call.lock().await.enqueue(track).await;
+        handle
+            .typemap()
+            .write()
+            .await
+            .insert::<TrackInfoKey>(Arc::new(track_info));
+
+        let started_instead = self.queue.modify_queue(|queue| {
+            let queued = queue.pop_back()?;
+            match queue.iter().position(|queued| queued.uuid() == failed_id) {
+                // the failed track is dropped once it ends, the new one follows
+                Some(0) => {
+                    queue.insert(1, queued);
+                    None
+                }
+                // it failed before its turn
+                Some(index) => {
+                    queue.remove(index);
+                    queue.insert(index, queued);
+                    None
+                }
+                // already dropped, the track after it started, and its play
+                // is only recorded once thanks to `count_play`
+                None => {
+                    let started_instead = queue.front().map(|queued| queued.handle());
+                    queue.push_front(queued);
+                    started_instead
+                }
+            }
+        });
+        if let Some(started_instead) = started_instead {
+            if let Err(e) = started_instead.pause() {
+                warn!("can't hold back the track after the failed one: {}", e);
+            }
+            if let Err(e) = handle.play() {
+                warn!("can't play the track again: {}", e);
+            }
+        }
+    }
+
+    /// Get the [`TrackInfo`] attached to a track handle.
+    pub async fn track_info(&self, handle: &TrackHandle) -> Option<Arc<TrackInfo>> {
+        let _queue_lock = self.queue_lock.lock().await;
+        handle.typemap().read().await.get::<TrackInfoKey>().cloned()
+    }
+
+    /// Get every track in the queue, the playing one first.
+    pub async fn tracks(&self) -> Vec<(TrackHandle, Arc<TrackInfo>)> {
+        let _queue_lock = self.queue_lock.lock().await;
+        let mut tracks = Vec::new();
+        for handle in self.queue.current_queue() {
+            let track_info = handle.typemap().read().await.get::<TrackInfoKey>().cloned();
+            if let Some(track_info) = track_info {
+                tracks.push((handle, track_info));
+            }
+        }
+        tracks
+    }
+
+    /// Get the playing track.
+    pub async fn current(&self) -> Option<(TrackHandle, Arc<TrackInfo>)> {
+        let handle = self.queue.current()?;
+        let track_info = self.track_info(&handle).await?;
+        Some((handle, track_info))
+    }
+
+    pub async fn loop_mode(&self) -> LoopMode {
+        self.state.lock().await.loop_mode
+    }
+
+    /// Change the loop mode, applying it to the playing track.
+    pub async fn set_loop_mode(&self, loop_mode: LoopMode) {
+        self.state.lock().await.loop_mode = loop_mode;
+        if let Some(handle) = self.queue.current() {
+            let result = match loop_mode {
+                LoopMode::Track => handle.enable_loop(),
+                LoopMode::Off | LoopMode::Queue => handle.disable_loop(),
+            };
+            if let Err(e) = result {
+                warn!("can't change looping of the current track: {}", e);
+            }
+        }
+    }
+
+    pub async fn volume_percent(&self) -> u32 {
+        self.state.lock().await.volume_percent
+    }
+
+    /// Change the volume of every track in the queue, returns the new volume.
+    pub async fn set_volume_percent(&self, volume_percent: u32) -> u32 {
+        let volume_percent = volume_percent.min(MAX_VOLUME_PERCENT);
+        self.state.lock().await.volume_percent = volume_percent;
+        for handle in self.queue.current_queue() {
+            // tracks that haven't started yet might not be in the mixer
+            handle.set_volume(volume_percent as f32 / 100.0).ok();
+        }
+        volume_percent
+    }
+
+    /// Stop the playing track, the next one starts right away.
+    pub fn skip(&self) -> TrackResult<()> {
+        self.queue.skip()
+    }
+
+    /// Take an upcoming track out of the queue, returns its [`TrackInfo`].
+    /// The playing track can only be skipped.
+    pub async fn remove(&self, track_id: Uuid) -> Option<Arc<TrackInfo>> {
+        let _queue_lock = self.queue_lock.lock().await;
+        let queued = self.queue.modify_queue(|queue| {
+            let index = queue
+                .iter()
+                .skip(1)
+                .position(|queued| queued.uuid() == track_id)?;
+            queue.remove(index + 1)
+        })?;
+
+        // without its track info, the ending track isn't put back in the queue
+        let track_info = queued.typemap().write().await.remove::<TrackInfoKey>();
+        if let Err(e) = queued.stop() {
+            warn!("can't stop the removed track: {}", e);
+        }
+        track_info
+    }
+
+    /// Shuffle the upcoming tracks, the playing one stays where it is.
+    pub async fn shuffle(&self) {
+        let _queue_lock = self.queue_lock.lock().await;
+        self.queue.modify_queue(|queue| {
+            if queue.len() > 2 {
+                queue.make_contiguous()[1..].shuffle(&mut rand::thread_rng());
+            }
+        });
+    }
+
+    /// Stop playing, empty the queue and cancel every running import.
+    pub async fn stop(&self) {
+        // or the ending tracks would be put back in the queue
+        self.state.lock().await.loop_mode = LoopMode::Off;
+        self.cancel_imports();
+        self.queue.stop();
+    }
+
+    /// Pause the playing track before leaving the voice channel, so that
+    /// it goes on from there once the bot joins again.
+    pub async fn pause_for_leave(&self) {
+        let handle = match self.queue.current() {
+            Some(handle) => handle,
+            None => return,
+        };
+        if !matches!(handle.get_info().await, Ok(info) if info.playing == PlayMode::Play) {
+            return;
+        }
+        match handle.pause() {
+            Ok(_) => self.state.lock().await.paused_by_leave = true,
+            Err(e) => warn!("can't pause the current track: {}", e),
+        }
+    }
+
+    /// Note that a track started playing, returns whether it's the first
+    /// time it did.
+    pub async fn count_play(&self, track_id: Uuid) -> bool {
+        self.state.lock().await.counted_plays.insert(track_id)
+    }
+
+    /// Give what a failed track had to the track playing it again: its
+    /// recorded play and its panel.
+    async fn hand_over(&self, failed_id: Uuid, new_id: Uuid) {
+        let mut state = self.state.lock().await;
+        if state.counted_plays.remove(&failed_id) {
+            state.counted_plays.insert(new_id);
+        }
+        if let Some(panel) = state
+            .panel
+            .as_mut()
+            .filter(|panel| panel.track_id == failed_id)
+        {
+            panel.track_id = new_id;
+        }
+    }
+
+    /// Forget about an ended track's play.
+    pub async fn forget_play(&self, track_id: Uuid) {
+        self.state.lock().await.counted_plays.remove(&track_id);
+    }
+
+    /// Note that the bot is about to leave its voice channel on purpose.
+    pub async fn start_leaving(&self) {
+        self.state.lock().await.leaving = true;
+    }
+
+    /// Whether the bot left its voice channel on purpose, forgetting it.
+    pub async fn take_leaving(&self) -> bool {
+        std::mem::take(&mut self.state.lock().await.leaving)
+    }
+
+    /// Resume what [`Self::pause_for_leave`] paused.
+    pub async fn resume_after_join(&self) {
+        if !std::mem::take(&mut self.state.lock().await.paused_by_leave) {
+            return;
+        }
+        if let Some(handle) = self.queue.current() {
+            if let Err(e) = handle.play() {
+                warn!("can't resume the current track: {}", e);
+            }
+        }
+    }
+
+    pub async fn panel(&self) -> Option<NowPlayingPanel> {
+        self.state.lock().await.panel
+    }
+
+    /// Set the current "Now playing" panel, returns the previous one.
+    pub async fn replace_panel(&self, panel: Option<NowPlayingPanel>) -> Option<NowPlayingPanel> {
+        std::mem::replace(&mut self.state.lock().await.panel, panel)
+    }
+
+    /// Add something that just started playing to the history.
+    pub async fn record_history(&self, title: String, url: String) {
+        let played_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs();
+        let history = &mut self.state.lock().await.history;
+        if history.len() == MAX_HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back(HistoryEntry {
+            title,
+            url,
+            played_at,
+        });
+    }
+
+    /// What played in the guild, the latest first.
+    pub async fn history(&self) -> Vec<HistoryEntry> {
+        self.state
+            .lock()
+            .await
+            .history
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    /// Start a sleep timer ending at `ends_at`, replacing the running one.
+    /// Returns its ID and a token cancelled along with it or by /nuke.
+    pub async fn start_sleep_timer(&self, ends_at: u64) -> (Uuid, CancellationToken) {
+        let id = Uuid::new_v4();
+        let cancel_token = self.cancel_token.child_token();
+        let previous = self.state.lock().await.sleep_timer.replace(SleepTimer {
+            id,
+            ends_at,
+            cancel_token: cancel_token.clone(),
+        });
+        if let Some(previous) = previous {
+            previous.cancel_token.cancel();
+        }
+        (id, cancel_token)
+    }
+
+    /// When the running sleep timer ends.
+    pub async fn sleep_timer(&self) -> Option<u64> {
+        self.state
+            .lock()
+            .await
+            .sleep_timer
+            .as_ref()
+            .filter(|sleep_timer| !sleep_timer.cancel_token.is_cancelled())
+            .map(|sleep_timer| sleep_timer.ends_at)
+    }
+
+    /// Cancel the running sleep timer, returns whether there was one.
+    pub async fn cancel_sleep_timer(&self) -> bool {
+        match self.state.lock().await.sleep_timer.take() {
+            Some(sleep_timer) => {
+                sleep_timer.cancel_token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forget about a sleep timer that went off, unless another one
+    /// replaced it.
+    pub async fn end_sleep_timer(&self, id: Uuid) {
+        let sleep_timer = &mut self.state.lock().await.sleep_timer;
+        if sleep_timer
+            .as_ref()
+            .is_some_and(|sleep_timer| sleep_timer.id == id)
+        {
+            *sleep_timer = None;
+        }
+    }
+
+    /// Turn the volume of the playing track down to nothing over `duration`.
+    /// Returns false, with the volume back where it was, when `cancel_token`
+    /// gets cancelled first.
+    pub async fn fade_out(&self, duration: Duration, cancel_token: &CancellationToken) -> bool {
+        const STEPS: u32 = 20;
+        let handle = match self.queue.current() {
+            Some(handle) => handle,
+            None => return true,
+        };
+        let volume = self.volume_percent().await as f32 / 100.0;
+        for step in (0..STEPS).rev() {
+            tokio::select! {
+                _ = tokio::time::sleep(duration / STEPS) => {}
+                _ = cancel_token.cancelled() => {
+                    handle.set_volume(volume).ok();
+                    return false;
+                }
+            }
+            // the track might end while fading, nothing left to do then
+            if handle
+                .set_volume(volume * step as f32 / STEPS as f32)
+                .is_err()
+            {
+                break;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn retries_keep_the_play_and_panel() {
+        let guild_player = GuildPlayer::new(TrackQueue::new());
+        let (failed_id, new_id) = (Uuid::new_v4(), Uuid::new_v4());
+        assert!(guild_player.count_play(failed_id).await);
+        guild_player
+            .replace_panel(Some(NowPlayingPanel {
+                track_id: failed_id,
+                channel_id: ChannelId::new(1),
+                message_id: MessageId::new(1),
+            }))
+            .await;
+
+        guild_player.hand_over(failed_id, new_id).await;
+        assert!(!guild_player.count_play(new_id).await);
+        assert_eq!(
+            guild_player.panel().await.map(|panel| panel.track_id),
+            Some(new_id)
+        );
+        // the failed track ending doesn't take the play away
+        guild_player.forget_play(failed_id).await;
+        assert!(!guild_player.count_play(new_id).await);
+    }
+
+    #[tokio::test]
+    async fn retries_of_unplayed_tracks_still_count() {
+        let guild_player = GuildPlayer::new(TrackQueue::new());
+        let (failed_id, new_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let other_panel = NowPlayingPanel {
+            track_id: Uuid::new_v4(),
+            channel_id: ChannelId::new(1),
+            message_id: MessageId::new(1),
+        };
+        guild_player.replace_panel(Some(other_panel)).await;
+
+        guild_player.hand_over(failed_id, new_id).await;
+        assert!(guild_player.count_play(new_id).await);
+        assert_eq!(
+            guild_player.panel().await.map(|panel| panel.track_id),
+            Some(other_panel.track_id)
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use super::track_info::StreamTitle;
+
+use std::{
+    io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult, SeekFrom},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use poise::{
+    futures_util::{Stream, StreamExt, TryStreamExt},
+    serenity_prelude::async_trait,
+};
+use reqwest::header::CONTENT_TYPE;
+use songbird::input::{AsyncAdapterStream, AsyncMediaSource, AudioStream, AudioStreamError};
+use symphonia::core::{io::MediaSource, probe::Hint};
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+/// Asks Icecast and Shoutcast servers to interleave song titles in the audio.
+pub const ICY_METADATA_HEADER: &str = "Icy-MetaData";
+/// How many bytes of audio there are between two metadata blocks.
+const ICY_METAINT_HEADER: &str = "icy-metaint";
+
+/// Where the next bytes of the stream belong.
+enum Block {
+    /// Audio, with how much of it is left before the next metadata block.
+    Audio(usize),
+    /// The byte giving the length of the next metadata block.
+    MetadataLength,
+    /// Metadata, with how much of it is left.
+    Metadata(usize),
+}
+
+/// A radio stream with its ICY metadata taken out of the audio, song titles
+/// going to a [`StreamTitle`].
+struct IcyStream {
+    chunks: Pin<Box<dyn Stream<Item = IoResult<Vec<u8>>> + Send + Sync>>,
+    chunk: Vec<u8>,
+    position: usize,
+    /// `None` when the server doesn't send metadata, everything is audio.
+    metaint: Option<usize>,
+    block: Block,
+    metadata: Vec<u8>,
+    stream_title: StreamTitle,
+}
+
+/// Read the song title out of a metadata block, like
+/// `StreamTitle='Artist - Song';StreamUrl='';`.
+fn parse_stream_title(metadata: &[u8]) -> Option<String> {
+    let metadata = String::from_utf8_lossy(metadata);
+    let start = metadata.find("StreamTitle='")? + "StreamTitle='".len();
+    let title = match metadata[start..].find("';") {
+        Some(end) => &metadata[start..start + end],
+        None => metadata[start..].trim_end_matches(['\0', '\'', ';']),
+    }
+    .trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+impl AsyncRead for IcyStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        let this = &mut *self;
+        loop {
+            if this.position == this.chunk.len() {
+                match this.chunks.poll_next_unpin(cx) {
+                    Poll::Ready(Some(Ok(chunk))) => {
+                        this.chunk = chunk;
+                        this.position = 0;
+                        continue;
+                    }
+                    Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(e)),
+                    // end of the stream
+                    Poll::Ready(None) => return Poll::Ready(Ok(())),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            let available = &this.chunk[this.position..];
+
+            match this.block {
+                Block::Audio(0) => this.block = Block::MetadataLength,
+                Block::Audio(left) => {
+                    let length = left.min(available.len()).min(buf.remaining());
+                    buf.put_slice(&available[..length]);
+                    this.position += length;
+                    if this.metaint.is_some() {
+                        this.block = Block::Audio(left - length);
+                    }
+                    return Poll::Ready(Ok(()));
+                }
+                Block::MetadataLength => {
+                    let length = available[0] as usize * 16;
+                    this.position += 1;
+                    this.block = match length {
+                        0 => Block::Audio(this.metaint.unwrap_or(usize::MAX)),
+                        length => Block::Metadata(length),
+                    };
+                }
+                Block::Metadata(left) => {
+                    let length = left.min(available.len());
+                    this.metadata.extend_from_slice(&available[..length]);
+                    this.position += length;
+                    this.block = match left - length {
+                        0 => {
+                            let title = parse_stream_title(&this.metadata);
+                            this.stream_title.set(title);
+                            this.metadata.clear();
+                            Block::Audio(this.metaint.unwrap_or(usize::MAX))
+                        }
+                        left => Block::Metadata(left),
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl AsyncSeek for IcyStream {
+    fn start_seek(self: Pin<&mut Self>, _position: SeekFrom) -> IoResult<()> {
+        Err(IoErrorKind::Unsupported.into())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IoResult<u64>> {
+        Poll::Ready(Err(IoErrorKind::Unsupported.into()))
+    }
+}
+
+#[async_trait]
+impl AsyncMediaSource for IcyStream {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    async fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Open a radio stream, following its song titles into `stream_title`.
+pub async fn open(
+    http_client: &reqwest::Client,
+    url: &str,
+    stream_title: StreamTitle,
+) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+    let response = http_client
+        .get(url)
+        .header(ICY_METADATA_HEADER, "1")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+
+    let headers = response.headers();
+    let hint = headers
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .map(|content_type| {
+            let mut hint = Hint::new();
+            hint.mime_type(content_type);
+            hint
+        });
+    let metaint = headers
+        .get(ICY_METAINT_HEADER)
+        .and_then(|metaint| metaint.to_str().ok())
+        .and_then(|metaint| metaint.trim().parse().ok())
+        .filter(|metaint| *metaint > 0);
+
+    let stream = IcyStream {
+        chunks: Box::pin(
+            response
+                .bytes_stream()
+                .map_ok(|chunk| chunk.to_vec())
+                .map_err(IoError::other),
+        ),
+        chunk: Vec::new(),
+        position: 0,
+        metaint,
+        block: Block::Audio(metaint.unwrap_or(usize::MAX)),
+        metadata: Vec::new(),
+        stream_title,
+    };
+    Ok(AudioStream {
+        input: Box::new(AsyncAdapterStream::new(Box::new(stream), 64 * 1024)),
+        hint,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_titles() {
+        assert_eq!(
+            parse_stream_title(b"StreamTitle='Artist - Song';StreamUrl='';\0\0"),
+            Some("Artist - Song".to_string())
+        );
+        assert_eq!(
+            parse_stream_title(b"StreamTitle='It's here';"),
+            Some("It's here".to_string())
+        );
+        // cut off by the end of the block
+        assert_eq!(
+            parse_stream_title(b"StreamTitle='Song\0\0\0"),
+            Some("Song".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_missing_titles() {
+        assert_eq!(parse_stream_title(b"StreamTitle='';"), None);
+        assert_eq!(
+            parse_stream_title(b"StreamUrl='https://example.com';"),
+            None
+        );
+        assert_eq!(parse_stream_title(b"\0\0\0\0"), None);
+    }
+}
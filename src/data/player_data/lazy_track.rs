@@ -0,0 +1,189 @@
+use super::{resolver::Source, PlayerData, TrackInfo};
+
+use std::{io::Cursor, process::Stdio, sync::Arc, time::Duration};
+
+use poise::serenity_prelude::async_trait;
+use songbird::input::{
+    AudioStream, AudioStreamError, AuxMetadata, ChildContainer, Compose, File, HttpRequest,
+};
+use symphonia::core::{
+    io::{MediaSource, ReadOnlySource},
+    probe::Hint,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// Find something playable for a track, with the resolver it came from.
+async fn prepare(player_data: &PlayerData, track_info: &TrackInfo) -> Result<Source, String> {
+    player_data
+        .resolvers
+        .for_track(track_info)
+        .prepare(player_data, track_info)
+        .await
+}
+
+/// Resolve the next tracks ahead of time so they start without delay.
+/// Stops when the guild gets nuked, and does nothing if the same URL is
+/// already being prefetched.
+pub async fn prefetch(
+    player_data: Arc<PlayerData>,
+    cancel_token: CancellationToken,
+    track_info: TrackInfo,
+) {
+    if !player_data
+        .prefetching
+        .lock()
+        .await
+        .insert(track_info.url.clone())
+    {
+        return;
+    }
+
+    let prefetching = player_data
+        .resolvers
+        .for_track(&track_info)
+        .prefetch(&player_data, &track_info);
+    tokio::select! {
+        result = prefetching => {
+            if let Err(e) = result {
+                warn!("can't prefetch {}: {}", track_info.url, e);
+            }
+        }
+        _ = cancel_token.cancelled() => {}
+    }
+
+    player_data.prefetching.lock().await.remove(&track_info.url);
+}
+
+/// A queue entry that only knows the source URL and metadata of its track.
+/// The playable stream is resolved by songbird right before it starts.
+pub struct LazyTrack {
+    player_data: Arc<PlayerData>,
+    /// Stops resolving when the guild gets nuked.
+    cancel_token: CancellationToken,
+    track_info: TrackInfo,
+}
+
+impl LazyTrack {
+    pub fn new(
+        player_data: Arc<PlayerData>,
+        cancel_token: CancellationToken,
+        track_info: TrackInfo,
+    ) -> Self {
+        Self {
+            player_data,
+            cancel_token,
+            track_info,
+        }
+    }
+
+    /// Resolve the track unless the guild gets nuked first.
+    async fn prepare(&self) -> Result<Source, AudioStreamError> {
+        tokio::select! {
+            source = prepare(&self.player_data, &self.track_info) => source,
+            _ = self.cancel_token.cancelled() => Err("cancelled".to_string()),
+        }
+        .map_err(|e| AudioStreamError::Fail(e.into()))
+    }
+
+    async fn open(
+        &self,
+        source: Source,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        match source {
+            Source::Url(url) => {
+                HttpRequest::new(self.player_data.http_client.clone(), url)
+                    .create_async()
+                    .await
+            }
+            Source::File(path) => File::new(path).create_async().await,
+            Source::Live(url) => self.open_live(&url),
+            Source::Radio(url) => {
+                super::icy::open(
+                    &self.player_data.http_client,
+                    &url,
+                    self.track_info.stream_title.clone(),
+                )
+                .await
+            }
+            Source::Memory(bytes, extension) => {
+                let mut hint = Hint::new();
+                hint.with_extension(extension);
+                Ok(AudioStream {
+                    input: Box::new(Cursor::new(bytes)),
+                    hint: Some(hint),
+                })
+            }
+        }
+    }
+
+    /// Let ffmpeg deal with HLS playlists and reconnections, and hand
+    /// songbird a plain AAC stream.
+    fn open_live(&self, url: &str) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        let child = std::process::Command::new(&self.player_data.ffmpeg_path)
+            .args(["-hide_banner", "-loglevel", "error"])
+            .args(["-reconnect", "1", "-reconnect_streamed", "1"])
+            .args(["-reconnect_delay_max", "5"])
+            .args(["-i", url])
+            .args(["-vn", "-c:a", "aac", "-b:a", "192k", "-f", "adts", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+
+        let mut hint = Hint::new();
+        hint.with_extension("aac");
+        Ok(AudioStream {
+            input: Box::new(ReadOnlySource::new(ChildContainer::from(child))),
+            hint: Some(hint),
+        })
+    }
+}
+
+#[async_trait]
+impl Compose for LazyTrack {
+    fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        Err(AudioStreamError::Unsupported)
+    }
+
+    async fn create_async(
+        &mut self,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        let source = self.prepare().await?;
+
+        let was_url = matches!(source, Source::Url(_) | Source::Live(_) | Source::Radio(_));
+        match self.open(source).await {
+            Ok(stream) => Ok(stream),
+            // the direct URL most likely expired, resolve a new one once
+            Err(e) if was_url => {
+                warn!(
+                    "can't open direct URL of {}, re-resolving: {}",
+                    self.track_info.url, e
+                );
+                self.track_info.stream_url.clear();
+                let source = self.prepare().await?;
+                self.open(source).await
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn should_create_async(&self) -> bool {
+        true
+    }
+
+    async fn aux_metadata(&mut self) -> Result<AuxMetadata, AudioStreamError> {
+        Ok(AuxMetadata {
+            title: Some(self.track_info.get_title()),
+            artist: self.track_info.artist.clone(),
+            // no duration for livestreams, so songbird won't try to preload what's after them
+            duration: match self.track_info.is_live() {
+                true => None,
+                false => self.track_info.duration_in_sec.map(Duration::from_secs),
+            },
+            source_url: Some(self.track_info.url.clone()),
+            thumbnail: self.track_info.thumbnail.clone(),
+            ..Default::default()
+        })
+    }
+}
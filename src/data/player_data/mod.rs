@@ -1,45 +1,115 @@
+mod duplicates;
+mod format_policy;
+mod guild_player;
+mod icy;
+mod lazy_track;
+mod probe;
+mod resolver;
+mod track_cache;
 mod track_info;
+mod yt_dlp;
 
-pub use track_info::TrackInfo;
+pub use duplicates::{normalize_url, SeenTracks};
+pub use format_policy::FormatPolicy;
+pub use guild_player::{GuildPlayer, LoopMode, NowPlayingPanel, MAX_VOLUME_PERCENT};
+pub use lazy_track::{prefetch, LazyTrack};
+pub use probe::probe;
+pub use resolver::{ResolverKind, Resolvers, DOWNLOAD_FORMAT, RADIO_PREFIX};
+pub use track_cache::TrackCache;
+pub use track_info::{pretty_duration, Requester, TrackInfo};
+pub use yt_dlp::YtDlp;
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use crate::data::config::Config;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use poise::serenity_prelude::GuildId;
-use tokio::sync::{broadcast, Mutex};
-use uuid::Uuid;
+use songbird::Call;
+use tokio::sync::{Mutex, RwLock};
 
 #[derive(Debug)]
 pub struct PlayerData {
-    /// A "flag" to indicate if the track end global event handler has been
-    /// added for any given guild-channel, so that users can execute the play
-    /// command multiple times without creating duplicate event handlers.
-    pub call_global_event_handler_added: Mutex<HashSet<GuildId>>,
-
-    /// Mapping track ID to channel ID since it's the only thing
-    /// songbird's context provides inside the EventHandler trait.
-    pub track_2_guild: Mutex<HashMap<Uuid, GuildId>>,
-
-    /// Just for displaying the playlist in the message since songbird's
-    /// queue is out of order.
-    pub guild_2_tracks: Mutex<HashMap<GuildId, VecDeque<TrackInfo>>>,
+    /// The player of every guild the bot is playing in. Only locked to
+    /// look a guild up, each [`GuildPlayer`] has its own locks.
+    guild_players: RwLock<HashMap<GuildId, Arc<GuildPlayer>>>,
 
     /// The reqwest client used for downloading the track
     /// when yt-dlp being able to use playable direct url.
     pub http_client: reqwest::Client,
 
-    /// Send a signal when users execute /nuke, this is used to
-    /// stop the play commands that are currently fetching new tracks.
-    pub nuke_signal: broadcast::Sender<GuildId>,
+    /// Downloaded tracks, shared across guilds.
+    pub track_cache: TrackCache,
+
+    /// Runs `yt-dlp` to resolve and download tracks.
+    pub yt_dlp: YtDlp,
+
+    /// Picks which format of a track gets streamed.
+    pub format_policy: FormatPolicy,
+
+    /// Picks how queries are resolved and tracks are played.
+    pub resolvers: Resolvers,
+
+    /// Used to play livestreams.
+    pub ffmpeg_path: String,
+
+    /// How many upcoming tracks get their stream resolved in advance.
+    pub prefetch_count: usize,
+
+    /// Source URLs being prefetched, so that each is resolved only once.
+    pub prefetching: Mutex<HashSet<String>>,
 }
 
-impl Default for PlayerData {
-    fn default() -> Self {
+impl PlayerData {
+    pub async fn new(config: &Config) -> Self {
         Self {
-            call_global_event_handler_added: Mutex::new(HashSet::new()),
-            track_2_guild: Mutex::new(HashMap::new()),
-            guild_2_tracks: Mutex::new(HashMap::new()),
+            guild_players: RwLock::new(HashMap::new()),
             http_client: reqwest::Client::new(),
-            nuke_signal: broadcast::channel::<GuildId>(1).0,
+            track_cache: TrackCache::new(&config.cache_dir, config.cache_max_size).await,
+            yt_dlp: YtDlp::new(config),
+            format_policy: FormatPolicy::new(config),
+            resolvers: Resolvers::new(config),
+            ffmpeg_path: config.ffmpeg_path.clone(),
+            prefetch_count: config.prefetch_count,
+            prefetching: Mutex::new(HashSet::new()),
         }
     }
+
+    /// Get the player of a guild, if the bot is playing there.
+    pub async fn guild_player(&self, guild_id: GuildId) -> Option<Arc<GuildPlayer>> {
+        self.guild_players.read().await.get(&guild_id).cloned()
+    }
+
+    /// Get the player of a guild, creating it around the guild's call if
+    /// needed. Also returns whether it was just created, so that the caller
+    /// can add the global event handlers to the call exactly once.
+    pub async fn get_or_create_guild_player(
+        &self,
+        guild_id: GuildId,
+        call: &Mutex<Call>,
+    ) -> (Arc<GuildPlayer>, bool) {
+        if let Some(guild_player) = self.guild_player(guild_id).await {
+            return (guild_player, false);
+        }
+
+        let mut guild_players = self.guild_players.write().await;
+        // someone else might have created it while we waited for the lock
+        if let Some(guild_player) = guild_players.get(&guild_id) {
+            return (guild_player.clone(), false);
+        }
+        let queue = call.lock().await.queue().clone();
+        let guild_player = Arc::new(GuildPlayer::new(queue));
+        guild_players.insert(guild_id, guild_player.clone());
+        (guild_player, true)
+    }
+
+    /// Forget about the player of a guild, cancelling everything it was
+    /// doing.
+    pub async fn remove_guild_player(&self, guild_id: GuildId) -> Option<Arc<GuildPlayer>> {
+        let guild_player = self.guild_players.write().await.remove(&guild_id)?;
+        guild_player.cancel_token.cancel();
+        Some(guild_player)
+    }
 }
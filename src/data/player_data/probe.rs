@@ -0,0 +1,67 @@
+use symphonia::core::{
+    formats::FormatOptions,
+    io::{MediaSource, MediaSourceStream},
+    meta::{MetadataOptions, MetadataRevision, StandardTagKey},
+    probe::Hint,
+};
+
+/// What an audio file says about itself.
+#[derive(Debug, Default)]
+pub struct Probed {
+    pub duration_in_sec: Option<u64>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+}
+
+impl Probed {
+    fn read_tags(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            let slot = match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => &mut self.title,
+                Some(StandardTagKey::Artist) => &mut self.artist,
+                _ => continue,
+            };
+            let value = tag.value.to_string();
+            if slot.is_none() && !value.trim().is_empty() {
+                *slot = Some(value);
+            }
+        }
+    }
+}
+
+/// Read the duration and tags of an audio file with symphonia, without
+/// decoding it. Blocking, so run it with `spawn_blocking`.
+pub fn probe(source: Box<dyn MediaSource>, hint: Hint) -> Result<Probed, String> {
+    let mut probe_result = symphonia::default::get_probe()
+        .format(
+            &hint,
+            MediaSourceStream::new(source, Default::default()),
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| format!("can't probe the audio: {}", e))?;
+
+    let mut probed = Probed {
+        duration_in_sec: probe_result.format.default_track().and_then(|track| {
+            let params = &track.codec_params;
+            let frames = params.n_frames?;
+            match params.time_base {
+                Some(time_base) => Some(time_base.calc_time(frames).seconds),
+                None => Some(frames / u64::from(params.sample_rate?)),
+            }
+        }),
+        ..Default::default()
+    };
+
+    // tags in front of the container, like ID3, then the container's own
+    if let Some(metadata) = probe_result.metadata.get() {
+        if let Some(revision) = metadata.current() {
+            probed.read_tags(revision);
+        }
+    }
+    if let Some(revision) = probe_result.format.metadata().current() {
+        probed.read_tags(revision);
+    }
+
+    Ok(probed)
+}
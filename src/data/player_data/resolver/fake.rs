@@ -0,0 +1,217 @@
+use super::{PlayerData, Resolver, ResolverKind, Source, TrackInfo, TrackStream};
+
+use poise::{
+    futures_util::{stream, StreamExt},
+    serenity_prelude::async_trait,
+};
+use reqwest::Url;
+
+/// Queries starting with this make up tracks, to try the player without
+/// network nor `yt-dlp`: `fake://name?count=3&duration=10`.
+const PREFIX: &str = "fake://";
+
+const SAMPLE_RATE: u32 = 48_000;
+const MAX_COUNT: usize = 100;
+const MAX_DURATION_IN_SEC: u64 = 10 * 60;
+
+/// Parameters of a fake query or track URL.
+struct FakeQuery {
+    name: String,
+    count: usize,
+    duration_in_sec: u64,
+}
+
+impl FakeQuery {
+    fn parse(query: &str) -> Result<Self, String> {
+        let url = Url::parse(query).map_err(|e| format!("invalid fake URL {}: {}", query, e))?;
+        let mut fake_query = Self {
+            name: url.host_str().unwrap_or("track").to_string(),
+            count: 1,
+            duration_in_sec: 30,
+        };
+        for (key, value) in url.query_pairs() {
+            let invalid = |_| format!("invalid {} in {}", key, query);
+            match key.as_ref() {
+                "count" => fake_query.count = value.parse().map_err(invalid)?,
+                "duration" => fake_query.duration_in_sec = value.parse().map_err(invalid)?,
+                _ => (),
+            }
+        }
+        fake_query.count = fake_query.count.clamp(1, MAX_COUNT);
+        fake_query.duration_in_sec = fake_query.duration_in_sec.clamp(1, MAX_DURATION_IN_SEC);
+        Ok(fake_query)
+    }
+}
+
+/// A quiet 440Hz tone as a 16-bit mono WAV file.
+fn tone_wav(duration_in_sec: u64) -> Vec<u8> {
+    let sample_count = SAMPLE_RATE * duration_in_sec as u32;
+    let data_size = sample_count * 2;
+
+    let mut wav = Vec::with_capacity(44 + data_size as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    for n in 0..sample_count {
+        let phase = 2.0 * std::f32::consts::PI * 440.0 * n as f32 / SAMPLE_RATE as f32;
+        let sample = (phase.sin() * 0.1 * i16::MAX as f32) as i16;
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+/// Made-up tracks playing a tone, for testing.
+#[derive(Debug)]
+pub struct FakeResolver;
+
+#[async_trait]
+impl Resolver for FakeResolver {
+    fn kind(&self) -> ResolverKind {
+        ResolverKind::Fake
+    }
+
+    async fn handles(&self, _player_data: &PlayerData, query: &str) -> bool {
+        query.starts_with(PREFIX)
+    }
+
+    async fn resolve(&self, _player_data: &PlayerData, query: &str) -> Result<TrackStream, String> {
+        let fake_query = FakeQuery::parse(query)?;
+        let track_infos = (1..=fake_query.count)
+            .map(|n| {
+                let name = match fake_query.count {
+                    1 => fake_query.name.clone(),
+                    _ => format!("{}-{}", fake_query.name, n),
+                };
+                let mut track_info = TrackInfo::new(
+                    ResolverKind::Fake,
+                    format!("{}{}?duration={}", PREFIX, name, fake_query.duration_in_sec),
+                    format!("Fake track {}", name),
+                );
+                track_info.duration_in_sec = Some(fake_query.duration_in_sec);
+                Ok(track_info)
+            })
+            .collect::<Vec<_>>();
+        Ok(stream::iter(track_infos).boxed())
+    }
+
+    async fn prepare(
+        &self,
+        _player_data: &PlayerData,
+        track_info: &TrackInfo,
+    ) -> Result<Source, String> {
+        let fake_query = FakeQuery::parse(&track_info.url)?;
+        Ok(Source::Memory(tone_wav(fake_query.duration_in_sec), "wav"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{config::Config, player_data::probe};
+
+    use std::io::Cursor;
+
+    use poise::futures_util::TryStreamExt;
+    use symphonia::core::probe::Hint;
+
+    async fn player_data(fake_tracks: bool) -> PlayerData {
+        let dir = std::env::temp_dir().join(format!("taxer-test-{}", uuid::Uuid::new_v4()));
+        let mut config = Config::for_tests(&dir);
+        config.fake_tracks = fake_tracks;
+        PlayerData::new(&config).await
+    }
+
+    #[test]
+    fn parse_defaults() {
+        let fake_query = FakeQuery::parse("fake://song").unwrap();
+        assert_eq!(fake_query.name, "song");
+        assert_eq!(fake_query.count, 1);
+        assert_eq!(fake_query.duration_in_sec, 30);
+    }
+
+    #[test]
+    fn parse_clamps() {
+        let fake_query = FakeQuery::parse("fake://song?count=1000&duration=0").unwrap();
+        assert_eq!(fake_query.count, MAX_COUNT);
+        assert_eq!(fake_query.duration_in_sec, 1);
+
+        let fake_query = FakeQuery::parse("fake://song?count=0&duration=100000").unwrap();
+        assert_eq!(fake_query.count, 1);
+        assert_eq!(fake_query.duration_in_sec, MAX_DURATION_IN_SEC);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_numbers() {
+        assert!(FakeQuery::parse("fake://song?count=many").is_err());
+        assert!(FakeQuery::parse("fake://song?duration=-1").is_err());
+    }
+
+    #[test]
+    fn tone_is_as_long_as_asked() {
+        let mut hint = Hint::new();
+        hint.with_extension("wav");
+        let probed = probe(Box::new(Cursor::new(tone_wav(2))), hint).unwrap();
+        assert_eq!(probed.duration_in_sec, Some(2));
+    }
+
+    #[tokio::test]
+    async fn resolves_and_prepares_tracks() {
+        let player_data = player_data(true).await;
+        let query = "fake://song?count=3&duration=5";
+        let resolver = player_data.resolvers.for_query(&player_data, query).await;
+        assert_eq!(resolver.kind(), ResolverKind::Fake);
+
+        let track_infos = resolver
+            .resolve(&player_data, query)
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let urls = track_infos
+            .iter()
+            .map(|track_info| track_info.url.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            [
+                "fake://song-1?duration=5",
+                "fake://song-2?duration=5",
+                "fake://song-3?duration=5"
+            ]
+        );
+        assert_eq!(track_infos[0].get_title(), "Fake track song-1");
+        assert_eq!(track_infos[0].duration_in_sec, Some(5));
+        assert!(!track_infos[0].is_live());
+
+        let source = player_data
+            .resolvers
+            .for_track(&track_infos[0])
+            .prepare(&player_data, &track_infos[0])
+            .await
+            .unwrap();
+        match source {
+            Source::Memory(wav, "wav") => assert_eq!(wav.len(), 44 + 48_000 * 5 * 2),
+            _ => panic!("fake tracks are played from memory"),
+        }
+    }
+
+    #[tokio::test]
+    async fn only_made_up_when_enabled() {
+        let player_data = player_data(false).await;
+        let resolver = player_data
+            .resolvers
+            .for_query(&player_data, "fake://song")
+            .await;
+        assert_eq!(resolver.kind(), ResolverKind::YtDlp);
+    }
+}
@@ -0,0 +1,164 @@
+use super::{PlayerData, Resolver, ResolverKind, Source, TrackInfo, TrackStream, AUDIO_EXTENSIONS};
+use crate::data::player_data::probe::{probe, Probed};
+
+use std::time::Duration;
+
+use poise::{
+    futures_util::{stream, StreamExt},
+    serenity_prelude::async_trait,
+};
+use reqwest::{header::CONTENT_TYPE, Url};
+use songbird::input::{AudioStream, Compose, HttpRequest};
+use tracing::warn;
+
+/// How long the server gets to tell what an URL without extension is.
+const HEAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long reading the duration and tags of a file can take.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Sites `yt-dlp` extracts, whose pages are never audio files, so there's no
+/// asking their servers.
+const YT_DLP_HOSTS: &[&str] = &[
+    "youtube.com",
+    "youtu.be",
+    "soundcloud.com",
+    "bandcamp.com",
+    "vimeo.com",
+    "twitch.tv",
+    "mixcloud.com",
+    "dailymotion.com",
+    "nicovideo.jp",
+    "bilibili.com",
+    "tiktok.com",
+    "twitter.com",
+    "x.com",
+];
+
+/// Whether an URL is on one of [`YT_DLP_HOSTS`] or their subdomains.
+fn is_yt_dlp_host(url: &Url) -> bool {
+    let host = match url.host_str() {
+        Some(host) => host.to_lowercase(),
+        None => return false,
+    };
+    YT_DLP_HOSTS.iter().any(|known| {
+        host == *known
+            || host
+                .strip_suffix(known)
+                .is_some_and(|subdomain| subdomain.ends_with('.'))
+    })
+}
+
+/// The extension of the file an URL points to, lowercased.
+fn extension_of(url: &Url) -> Option<String> {
+    let file_name = url.path_segments()?.next_back()?;
+    let (_, extension) = file_name.rsplit_once('.')?;
+    Some(extension.to_lowercase())
+}
+
+/// Whether a Content-Type is audio that can be played as it is. Playlists
+/// like M3U and PLS are left to `yt-dlp`.
+pub(super) fn is_audio_content_type(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    match mime.strip_prefix("audio/") {
+        Some(subtype) => !subtype.contains("mpegurl") && !subtype.contains("scpls"),
+        None => mime == "application/ogg",
+    }
+}
+
+/// Direct links to audio files, played without `yt-dlp`.
+#[derive(Debug)]
+pub struct HttpResolver;
+
+impl HttpResolver {
+    /// Read the duration and tags at the start of the file.
+    async fn probe(player_data: &PlayerData, url: &str) -> Result<Probed, String> {
+        let AudioStream { input, hint } =
+            HttpRequest::new(player_data.http_client.clone(), url.to_string())
+                .create_async()
+                .await
+                .map_err(|e| format!("can't open {}: {}", url, e))?;
+
+        let probing = tokio::task::spawn_blocking(move || probe(input, hint.unwrap_or_default()));
+        tokio::time::timeout(PROBE_TIMEOUT, probing)
+            .await
+            .map_err(|_| format!("took too long to probe {}", url))?
+            .map_err(|e| format!("can't probe {}: {}", url, e))?
+    }
+}
+
+#[async_trait]
+impl Resolver for HttpResolver {
+    fn kind(&self) -> ResolverKind {
+        ResolverKind::Http
+    }
+
+    async fn handles(&self, player_data: &PlayerData, query: &str) -> bool {
+        let url = match Url::parse(query) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+            _ => return false,
+        };
+        if extension_of(&url)
+            .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension.as_str()))
+        {
+            return true;
+        }
+        if is_yt_dlp_host(&url) {
+            return false;
+        }
+
+        // no telling from the name, ask the server
+        match player_data
+            .http_client
+            .head(url)
+            .timeout(HEAD_TIMEOUT)
+            .send()
+            .await
+        {
+            Ok(response) if response.status().is_success() => response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|content_type| content_type.to_str().ok())
+                .is_some_and(is_audio_content_type),
+            _ => false,
+        }
+    }
+
+    async fn resolve(&self, player_data: &PlayerData, query: &str) -> Result<TrackStream, String> {
+        let url = Url::parse(query).map_err(|e| format!("invalid URL {}: {}", query, e))?;
+        let file_name = url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .filter(|file_name| !file_name.is_empty())
+            .unwrap_or(query)
+            .to_string();
+
+        // still playable without tags, just with less to show
+        let probed = Self::probe(player_data, query).await.unwrap_or_else(|e| {
+            warn!("{}", e);
+            Default::default()
+        });
+
+        let mut track_info = TrackInfo::new(
+            ResolverKind::Http,
+            query.to_string(),
+            probed.title.unwrap_or(file_name),
+        );
+        track_info.duration_in_sec = probed.duration_in_sec;
+        track_info.artist = probed.artist;
+        Ok(stream::iter([Ok(track_info)]).boxed())
+    }
+
+    async fn prepare(
+        &self,
+        _player_data: &PlayerData,
+        track_info: &TrackInfo,
+    ) -> Result<Source, String> {
+        Ok(Source::Url(track_info.url.clone()))
+    }
+}
@@ -0,0 +1,192 @@
+use super::{PlayerData, Resolver, ResolverKind, Source, TrackInfo, TrackStream, AUDIO_EXTENSIONS};
+use crate::data::player_data::probe::probe;
+
+use std::path::{Path, PathBuf};
+
+use poise::{
+    futures_util::{stream, StreamExt},
+    serenity_prelude::async_trait,
+};
+use symphonia::core::probe::Hint;
+use tracing::warn;
+
+/// Queries starting with this are paths or searches in the music library.
+const PREFIX: &str = "local:";
+
+/// Most tracks a folder or a search in the library adds at once.
+const MAX_TRACKS: usize = 200;
+
+fn is_audio_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+/// Every audio file under a folder, sorted by path.
+fn audio_files_in(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("can't read {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => dirs.push(path),
+                Ok(_) if is_audio_file(&path) => files.push(path),
+                _ => (),
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Audio files of a local music library, given by `MUSIC_DIR`.
+#[derive(Debug)]
+pub struct LocalResolver {
+    music_dir: Option<PathBuf>,
+}
+
+impl LocalResolver {
+    pub fn new(music_dir: Option<&str>) -> Self {
+        Self {
+            music_dir: music_dir.map(PathBuf::from),
+        }
+    }
+
+    fn music_dir(&self) -> Result<PathBuf, String> {
+        let music_dir = self
+            .music_dir
+            .as_ref()
+            .ok_or("There's no local music library.".to_string())?;
+        music_dir
+            .canonicalize()
+            .map_err(|e| format!("can't open {}: {}", music_dir.display(), e))
+    }
+
+    /// The files a query is about: a file, the audio files of a folder, or
+    /// the audio files whose path contains the query.
+    fn find(music_dir: &Path, query: &str) -> Vec<PathBuf> {
+        let query = query.trim().trim_start_matches('/');
+
+        // paths must stay in the library, `..` and symlinks included
+        if let Ok(path) = music_dir.join(query).canonicalize() {
+            if path.starts_with(music_dir) {
+                if is_audio_file(&path) {
+                    return vec![path];
+                }
+                if path.is_dir() {
+                    return audio_files_in(&path).into_iter().take(MAX_TRACKS).collect();
+                }
+            }
+        }
+
+        let query = query.to_lowercase();
+        audio_files_in(music_dir)
+            .into_iter()
+            .filter(|path| {
+                path.strip_prefix(music_dir)
+                    .is_ok_and(|path| path.to_string_lossy().to_lowercase().contains(&query))
+            })
+            .take(MAX_TRACKS)
+            .collect()
+    }
+
+    /// Describe a file of the library from its tags, or its name.
+    fn track_info(music_dir: &Path, path: &Path) -> TrackInfo {
+        let relative_path = path.strip_prefix(music_dir).unwrap_or(path);
+        let file_name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let probed = std::fs::File::open(path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| {
+                let mut hint = Hint::new();
+                if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+                    hint.with_extension(extension);
+                }
+                probe(Box::new(file), hint)
+            })
+            .unwrap_or_else(|e| {
+                warn!("can't probe {}: {}", path.display(), e);
+                Default::default()
+            });
+
+        let mut track_info = TrackInfo::new(
+            ResolverKind::Local,
+            format!("{}{}", PREFIX, relative_path.display()),
+            probed.title.unwrap_or(file_name),
+        );
+        track_info.duration_in_sec = probed.duration_in_sec;
+        track_info.artist = probed.artist;
+        track_info
+    }
+}
+
+#[async_trait]
+impl Resolver for LocalResolver {
+    fn kind(&self) -> ResolverKind {
+        ResolverKind::Local
+    }
+
+    async fn handles(&self, _player_data: &PlayerData, query: &str) -> bool {
+        query.starts_with(PREFIX)
+    }
+
+    async fn resolve(&self, _player_data: &PlayerData, query: &str) -> Result<TrackStream, String> {
+        let music_dir = self.music_dir()?;
+        let query = query[PREFIX.len()..].to_string();
+
+        let paths = {
+            let music_dir = music_dir.clone();
+            let query = query.clone();
+            tokio::task::spawn_blocking(move || Self::find(&music_dir, &query))
+                .await
+                .map_err(|e| format!("can't search the music library: {}", e))?
+        };
+        if paths.is_empty() {
+            return Err(format!("Nothing matches `{}` in the music library.", query));
+        }
+
+        Ok(stream::iter(paths)
+            .then(move |path| {
+                let music_dir = music_dir.clone();
+                async move {
+                    tokio::task::spawn_blocking(move || Self::track_info(&music_dir, &path))
+                        .await
+                        .map_err(|e| format!("can't read the music library: {}", e))
+                }
+            })
+            .boxed())
+    }
+
+    async fn prepare(
+        &self,
+        _player_data: &PlayerData,
+        track_info: &TrackInfo,
+    ) -> Result<Source, String> {
+        let music_dir = self.music_dir()?;
+        let relative_path = track_info
+            .url
+            .strip_prefix(PREFIX)
+            .ok_or(format!("not a local track: {}", track_info.url))?;
+        let path = music_dir
+            .join(relative_path)
+            .canonicalize()
+            .map_err(|e| format!("can't find {}: {}", track_info.url, e))?;
+        match path.starts_with(&music_dir) {
+            true => Ok(Source::File(path)),
+            false => Err(format!("{} is out of the music library", track_info.url)),
+        }
+    }
+}
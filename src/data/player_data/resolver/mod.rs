@@ -0,0 +1,123 @@
+mod fake;
+mod http;
+mod local;
+mod radio;
+mod yt_dlp;
+
+pub use radio::PREFIX as RADIO_PREFIX;
+pub use yt_dlp::DOWNLOAD_FORMAT;
+
+use super::{PlayerData, TrackInfo};
+use crate::data::config::Config;
+
+use std::{fmt::Debug, path::PathBuf};
+
+use poise::{futures_util::stream::BoxStream, serenity_prelude::async_trait};
+use serde::{Deserialize, Serialize};
+
+/// Extensions of audio files that can be played as they are.
+const AUDIO_EXTENSIONS: [&str; 9] = [
+    "mp3", "flac", "ogg", "oga", "opus", "wav", "aac", "mka", "webm",
+];
+
+/// Tracks found for a query, in order.
+pub type TrackStream = BoxStream<'static, Result<TrackInfo, String>>;
+
+/// Which [`Resolver`] a track came from, so that the same one prepares it
+/// for playing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolverKind {
+    #[default]
+    YtDlp,
+    Http,
+    Local,
+    Radio,
+    Fake,
+}
+
+/// Where the audio of a track can be read from.
+pub enum Source {
+    Url(String),
+    File(PathBuf),
+    /// A never-ending stream, remuxed by ffmpeg.
+    Live(String),
+    /// An Icecast or Shoutcast station, with song titles in the stream.
+    Radio(String),
+    /// Audio held in memory, with its file extension.
+    Memory(Vec<u8>, &'static str),
+}
+
+/// Turns queries into tracks, and tracks into something playable.
+#[async_trait]
+pub trait Resolver: Debug + Send + Sync {
+    fn kind(&self) -> ResolverKind;
+
+    /// Whether the query is for this resolver, from its URL scheme or host,
+    /// or what the server says about it.
+    async fn handles(&self, player_data: &PlayerData, query: &str) -> bool;
+
+    /// Find the tracks of a query.
+    async fn resolve(&self, player_data: &PlayerData, query: &str) -> Result<TrackStream, String>;
+
+    /// Find something playable for one of this resolver's tracks, right
+    /// before it plays.
+    async fn prepare(
+        &self,
+        player_data: &PlayerData,
+        track_info: &TrackInfo,
+    ) -> Result<Source, String>;
+
+    /// Get ahead of [`Resolver::prepare`] for a track coming up, with what's
+    /// quick to undo: resolving a URL, never downloading.
+    async fn prefetch(
+        &self,
+        _player_data: &PlayerData,
+        _track_info: &TrackInfo,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Every resolver, `yt-dlp` taking whatever the others don't.
+#[derive(Debug)]
+pub struct Resolvers {
+    resolvers: Vec<Box<dyn Resolver>>,
+    fallback: yt_dlp::YtDlpResolver,
+}
+
+impl Resolvers {
+    pub fn new(config: &Config) -> Self {
+        let mut resolvers: Vec<Box<dyn Resolver>> = vec![
+            Box::new(local::LocalResolver::new(config.music_dir.as_deref())),
+            Box::new(radio::RadioResolver),
+            Box::new(http::HttpResolver),
+        ];
+        if config.fake_tracks {
+            resolvers.insert(0, Box::new(fake::FakeResolver));
+        }
+        Self {
+            resolvers,
+            fallback: yt_dlp::YtDlpResolver,
+        }
+    }
+
+    /// The resolver a query should go through.
+    pub async fn for_query(&self, player_data: &PlayerData, query: &str) -> &dyn Resolver {
+        for resolver in &self.resolvers {
+            if resolver.handles(player_data, query).await {
+                return resolver.as_ref();
+            }
+        }
+        &self.fallback
+    }
+
+    /// The resolver a track came from.
+    pub fn for_track(&self, track_info: &TrackInfo) -> &dyn Resolver {
+        self.resolvers
+            .iter()
+            .find(|resolver| resolver.kind() == track_info.resolver)
+            .map(|resolver| resolver.as_ref())
+            .unwrap_or(&self.fallback)
+    }
+}
@@ -0,0 +1,146 @@
+use super::{
+    http::is_audio_content_type, PlayerData, Resolver, ResolverKind, Source, TrackInfo, TrackStream,
+};
+use crate::data::player_data::icy::ICY_METADATA_HEADER;
+
+use std::time::Duration;
+
+use poise::{
+    futures_util::{stream, StreamExt},
+    serenity_prelude::async_trait,
+};
+use reqwest::{header::CONTENT_TYPE, Response, Url};
+
+/// Queries starting with this are Icecast or Shoutcast stations, as used
+/// by /radio.
+pub const PREFIX: &str = "radio:";
+
+/// How long a station gets to answer.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Biggest station playlist (`.pls`, `.m3u`) read.
+const MAX_PLAYLIST_SIZE: usize = 64 * 1024;
+
+fn is_playlist(url: &Url, content_type: &str) -> bool {
+    let path = url.path().to_lowercase();
+    [".pls", ".m3u", ".m3u8"]
+        .iter()
+        .any(|extension| path.ends_with(extension))
+        || content_type.contains("mpegurl")
+        || content_type.contains("scpls")
+}
+
+/// Read up to [`MAX_PLAYLIST_SIZE`] of a playlist, stations sometimes
+/// serve their audio stream in its place.
+async fn read_playlist(mut response: Response) -> Result<Vec<u8>, String> {
+    let mut playlist = Vec::new();
+    while playlist.len() < MAX_PLAYLIST_SIZE {
+        match response
+            .chunk()
+            .await
+            .map_err(|e| format!("Can't read the station's playlist: {}", e))?
+        {
+            Some(chunk) => playlist.extend_from_slice(&chunk),
+            None => break,
+        }
+    }
+    playlist.truncate(MAX_PLAYLIST_SIZE);
+    Ok(playlist)
+}
+
+/// The first stream of a station playlist, PLS or M3U.
+fn first_stream_of(playlist: &str) -> Result<Url, String> {
+    if playlist.contains("#EXT-X-") {
+        return Err("That's an HLS stream, not a radio, use /play instead.".to_string());
+    }
+    playlist
+        .lines()
+        .map(|line| match line.trim().split_once('=') {
+            // PLS entries look like `File1=http://...`
+            Some((key, value)) if key.to_lowercase().starts_with("file") => value.trim(),
+            _ => line.trim(),
+        })
+        .find_map(|line| Url::parse(line).ok())
+        .ok_or("There's no stream in that playlist.".to_string())
+}
+
+/// Internet radios, played continuously with the song they're playing
+/// read from ICY metadata.
+#[derive(Debug)]
+pub struct RadioResolver;
+
+#[async_trait]
+impl Resolver for RadioResolver {
+    fn kind(&self) -> ResolverKind {
+        ResolverKind::Radio
+    }
+
+    async fn handles(&self, _player_data: &PlayerData, query: &str) -> bool {
+        query.starts_with(PREFIX)
+    }
+
+    async fn resolve(&self, player_data: &PlayerData, query: &str) -> Result<TrackStream, String> {
+        let mut url = Url::parse(query[PREFIX.len()..].trim())
+            .map_err(|e| format!("That's not a valid URL: {}", e))?;
+
+        // stations are often linked through a playlist, follow it once
+        let mut followed_playlist = false;
+        let response = loop {
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err("Radios must be HTTP or HTTPS streams.".to_string());
+            }
+            let response = player_data
+                .http_client
+                .get(url.clone())
+                .header(ICY_METADATA_HEADER, "1")
+                .timeout(CONNECT_TIMEOUT)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| format!("Can't reach the station: {}", e))?;
+            let content_type = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|content_type| content_type.to_str().ok())
+                .unwrap_or_default()
+                .to_lowercase();
+
+            if followed_playlist || !is_playlist(&url, &content_type) {
+                if !is_audio_content_type(&content_type) {
+                    return Err("That's not an audio stream.".to_string());
+                }
+                break response;
+            }
+            let playlist = read_playlist(response).await?;
+            url = first_stream_of(&String::from_utf8_lossy(&playlist))?;
+            followed_playlist = true;
+        };
+
+        // only the headers are needed, dropping the response closes the stream
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let mut track_info = TrackInfo::new(
+            ResolverKind::Radio,
+            url.to_string(),
+            header("icy-name").unwrap_or_else(|| url.host_str().unwrap_or("Radio").to_string()),
+        );
+        track_info.uploader = header("icy-genre");
+        track_info.is_live = Some(true);
+        Ok(stream::iter([Ok(track_info)]).boxed())
+    }
+
+    async fn prepare(
+        &self,
+        _player_data: &PlayerData,
+        track_info: &TrackInfo,
+    ) -> Result<Source, String> {
+        Ok(Source::Radio(track_info.url.clone()))
+    }
+}
@@ -0,0 +1,179 @@
+use super::{PlayerData, Resolver, ResolverKind, Source, TrackInfo, TrackStream};
+
+use std::time::Duration;
+
+use poise::{
+    futures_util::{stream, StreamExt},
+    serenity_prelude::async_trait,
+};
+use tracing::warn;
+
+/// Audio format of tracks downloaded when there's no playable direct URL.
+pub const DOWNLOAD_FORMAT: &str = "aac";
+
+/// Direct URLs from YouTube last for a few hours, re-resolve well before.
+const STREAM_URL_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// After failing to play this many times, even with a fresh direct URL,
+/// tracks get downloaded instead.
+const DOWNLOAD_AFTER_FAILURES: u32 = 2;
+
+/// Anything `yt-dlp` supports, and YouTube searches.
+#[derive(Debug)]
+pub struct YtDlpResolver;
+
+impl YtDlpResolver {
+    /// Resolve a fresh URL for a livestream, they can't be cached nor downloaded.
+    async fn prepare_live(
+        &self,
+        player_data: &PlayerData,
+        track_info: &TrackInfo,
+    ) -> Result<Source, String> {
+        if let Some(url) = track_info.stream_url.get_fresh(STREAM_URL_MAX_AGE) {
+            return Ok(Source::Live(url));
+        }
+
+        let mut fresh = player_data.yt_dlp.resolve_single(&track_info.url).await?;
+        fresh.prime_stream_url(&player_data.format_policy);
+        match fresh.stream_url.get_fresh(STREAM_URL_MAX_AGE) {
+            Some(url) => {
+                track_info.stream_url.set(url.clone());
+                Ok(Source::Live(url))
+            }
+            None => Err(format!("no playable stream for {}", track_info.url)),
+        }
+    }
+}
+
+#[async_trait]
+impl Resolver for YtDlpResolver {
+    fn kind(&self) -> ResolverKind {
+        ResolverKind::YtDlp
+    }
+
+    async fn handles(&self, _player_data: &PlayerData, _query: &str) -> bool {
+        true
+    }
+
+    async fn resolve(&self, player_data: &PlayerData, query: &str) -> Result<TrackStream, String> {
+        // already downloaded, no need to ask yt-dlp about it
+        if let Some(track_info) = player_data.track_cache.peek(query, DOWNLOAD_FORMAT).await {
+            return Ok(stream::iter([Ok(track_info)]).boxed());
+        }
+
+        let resolution = player_data.yt_dlp.resolve(query).await?;
+        Ok(stream::try_unfold(
+            (Some(resolution), false),
+            |(resolution, resolved_any)| async move {
+                let mut resolution = match resolution {
+                    Some(resolution) => resolution,
+                    None => return Ok(None),
+                };
+                match resolution.next_track().await? {
+                    Some(track_info) => Ok(Some((track_info, (Some(resolution), true)))),
+                    // wait for yt-dlp to finish
+                    None => match resolution.wait().await {
+                        Ok(()) => Ok(None),
+                        // some entries of a playlist can fail without
+                        // the others being lost
+                        Err(e) if resolved_any => {
+                            warn!("yt-dlp skipped some entries: {}", e);
+                            Ok(None)
+                        }
+                        Err(e) => Err(e),
+                    },
+                }
+            },
+        )
+        .boxed())
+    }
+
+    /// Find something playable for a track: a fresh direct URL, a cached
+    /// download, a newly resolved direct URL, or download it as a last resort
+    /// or when direct URLs keep failing.
+    async fn prepare(
+        &self,
+        player_data: &PlayerData,
+        track_info: &TrackInfo,
+    ) -> Result<Source, String> {
+        if track_info.is_live() {
+            return self.prepare_live(player_data, track_info).await;
+        }
+
+        let use_direct_url = track_info.failures < DOWNLOAD_AFTER_FAILURES;
+        if let Some(url) = track_info
+            .stream_url
+            .get_fresh(STREAM_URL_MAX_AGE)
+            .filter(|_| use_direct_url)
+        {
+            return Ok(Source::Url(url));
+        }
+
+        if let Some(path) = player_data
+            .track_cache
+            .get(&track_info.url, DOWNLOAD_FORMAT)
+            .await
+        {
+            return Ok(Source::File(path));
+        }
+
+        if use_direct_url {
+            let mut fresh = player_data.yt_dlp.resolve_single(&track_info.url).await?;
+            if fresh.prime_stream_url(&player_data.format_policy) {
+                if let Some(url) = fresh.stream_url.get_fresh(STREAM_URL_MAX_AGE) {
+                    track_info.stream_url.set(url.clone());
+                    return Ok(Source::Url(url));
+                }
+            }
+            warn!(
+                "no playable URL for {}, downloading the track instead",
+                track_info.url
+            );
+        } else {
+            warn!(
+                "{} failed to play {} times, downloading it instead",
+                track_info.url, track_info.failures
+            );
+        }
+        let download_stem = player_data
+            .track_cache
+            .download_stem(&track_info.url, DOWNLOAD_FORMAT);
+        let audio_path = player_data
+            .yt_dlp
+            .download(&track_info.url, &download_stem, DOWNLOAD_FORMAT)
+            .await?;
+        player_data
+            .track_cache
+            .insert(&track_info.url, DOWNLOAD_FORMAT, audio_path, track_info)
+            .await
+            .map(Source::File)
+    }
+
+    async fn prefetch(
+        &self,
+        player_data: &PlayerData,
+        track_info: &TrackInfo,
+    ) -> Result<(), String> {
+        // downloading is left to the track itself, when it can't do without
+        if track_info.failures >= DOWNLOAD_AFTER_FAILURES
+            || track_info
+                .stream_url
+                .get_fresh(STREAM_URL_MAX_AGE)
+                .is_some()
+            || player_data
+                .track_cache
+                .contains(&track_info.url, DOWNLOAD_FORMAT)
+                .await
+        {
+            return Ok(());
+        }
+
+        let mut fresh = player_data.yt_dlp.resolve_single(&track_info.url).await?;
+        if fresh.prime_stream_url(&player_data.format_policy) {
+            if let Some(url) = fresh.stream_url.get_fresh(STREAM_URL_MAX_AGE) {
+                track_info.stream_url.set(url);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,329 @@
+use super::TrackInfo;
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+use tokio::sync::Mutex;
+use tracing::warn;
+use uuid::Uuid;
+
+/// A downloaded track living in the cache directory.
+#[derive(Debug)]
+struct CacheEntry {
+    audio_path: PathBuf,
+    /// Size of the audio file and its metadata sidecar, in bytes.
+    size: u64,
+    /// Logical clock value of the last access, the smallest one gets evicted.
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+    total_size: u64,
+    clock: u64,
+}
+
+impl CacheIndex {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+/// Snapshot of the cache counters, for diagnostics.
+#[derive(Debug)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub total_size: u64,
+    pub max_size: u64,
+}
+
+/// Content cache of downloaded tracks shared by all guilds, keyed by the
+/// source URL and the audio format. Each entry is an audio file named
+/// `<key>.<ext>` next to a `<key>.json` sidecar holding its [`TrackInfo`],
+/// the least recently used entries are evicted once `max_size` is exceeded.
+#[derive(Debug)]
+pub struct TrackCache {
+    dir: PathBuf,
+    max_size: u64,
+    index: Mutex<CacheIndex>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl TrackCache {
+    /// Create a new [`TrackCache`], picking up entries left over from
+    /// previous runs in `dir`.
+    pub async fn new(dir: impl Into<PathBuf>, max_size: u64) -> Self {
+        let dir = dir.into();
+        if let Err(e) = tokio::fs::create_dir_all(&dir).await {
+            warn!("can't create cache dir {}: {}", dir.display(), e);
+        }
+
+        let scanned_dir = dir.clone();
+        let index = tokio::task::spawn_blocking(move || Self::scan(&scanned_dir))
+            .await
+            .unwrap_or_else(|e| {
+                warn!("can't scan cache dir: {}", e);
+                CacheIndex::default()
+            });
+
+        Self {
+            index: Mutex::new(index),
+            dir,
+            max_size,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Build the index from what's on disk, least recently used first so
+    /// that the LRU order survives restarts. Incomplete entries are removed.
+    fn scan(dir: &Path) -> CacheIndex {
+        let mut index = CacheIndex::default();
+
+        let files = match std::fs::read_dir(dir) {
+            Ok(files) => files
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                warn!("can't read cache dir {}: {}", dir.display(), e);
+                return index;
+            }
+        };
+
+        let mut found = files
+            .iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext != "json"))
+            .filter_map(|audio_path| {
+                let key = audio_path.file_stem()?.to_string_lossy().to_string();
+                let sidecar = dir.join(format!("{}.json", key));
+                let audio_meta = std::fs::metadata(audio_path).ok()?;
+                let sidecar_meta = std::fs::metadata(&sidecar).ok()?;
+                Some((
+                    key,
+                    audio_path.clone(),
+                    audio_meta.len() + sidecar_meta.len(),
+                    audio_meta.modified().ok(),
+                ))
+            })
+            .collect::<Vec<_>>();
+        found.sort_by_key(|(_, _, _, modified)| *modified);
+
+        for (key, audio_path, size, _) in found {
+            let last_used = index.tick();
+            index.total_size += size;
+            index.entries.insert(
+                key,
+                CacheEntry {
+                    audio_path,
+                    size,
+                    last_used,
+                },
+            );
+        }
+
+        // anything not indexed is a leftover from an interrupted download
+        for path in files {
+            let key = match path.file_stem() {
+                Some(stem) => stem.to_string_lossy().to_string(),
+                None => continue,
+            };
+            if !index.entries.contains_key(&key) {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    warn!("can't remove stale cache file {}: {}", path.display(), e);
+                }
+            }
+        }
+
+        index
+    }
+
+    /// Cache key of a source URL in the given audio format.
+    pub fn key(url: &str, format: &str) -> String {
+        Uuid::new_v5(
+            &Uuid::NAMESPACE_URL,
+            format!("{}#{}", url, format).as_bytes(),
+        )
+        .to_string()
+    }
+
+    /// Path without extension for `yt-dlp` to download a track into.
+    pub fn download_stem(&self, url: &str, format: &str) -> PathBuf {
+        self.dir.join(Self::key(url, format))
+    }
+
+    fn sidecar_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    async fn read_sidecar(&self, key: &str) -> Option<TrackInfo> {
+        let content = tokio::fs::read_to_string(self.sidecar_path(key))
+            .await
+            .ok()?;
+        match serde_json::from_str(&content) {
+            Ok(track_info) => Some(track_info),
+            Err(e) => {
+                warn!("can't parse cache sidecar of {}: {}", key, e);
+                None
+            }
+        }
+    }
+
+    /// Look up a track without counting it as a hit or a miss, so the
+    /// caller can skip resolving metadata for it.
+    pub async fn peek(&self, url: &str, format: &str) -> Option<TrackInfo> {
+        let key = Self::key(url, format);
+        if !self.index.lock().await.entries.contains_key(&key) {
+            return None;
+        }
+        self.read_sidecar(&key).await
+    }
+
+    /// Whether a track is downloaded, without counting it as a hit or a miss.
+    pub async fn contains(&self, url: &str, format: &str) -> bool {
+        let key = Self::key(url, format);
+        self.index.lock().await.entries.contains_key(&key)
+    }
+
+    /// Look up a track, marking it as recently used, on disk too for the
+    /// next [`TrackCache::scan`]. Returns the path of the audio file for
+    /// songbird to play.
+    pub async fn get(&self, url: &str, format: &str) -> Option<PathBuf> {
+        let key = Self::key(url, format);
+        let mut index = self.index.lock().await;
+
+        let cached = match index.entries.get(&key) {
+            Some(entry) => {
+                let audio_path = entry.audio_path.clone();
+                let exists = tokio::fs::try_exists(&audio_path).await.unwrap_or(false)
+                    && tokio::fs::try_exists(self.sidecar_path(&key))
+                        .await
+                        .unwrap_or(false);
+                exists.then_some(audio_path)
+            }
+            None => None,
+        };
+
+        match cached {
+            Some(path) => {
+                let last_used = index.tick();
+                if let Some(entry) = index.entries.get_mut(&key) {
+                    entry.last_used = last_used;
+                }
+                self.hits.fetch_add(1, Ordering::Relaxed);
+
+                let touched_path = path.clone();
+                tokio::task::spawn_blocking(move || {
+                    let touched = File::options()
+                        .write(true)
+                        .open(&touched_path)
+                        .and_then(|file| file.set_modified(SystemTime::now()));
+                    if let Err(e) = touched {
+                        warn!("can't touch cache file {}: {}", touched_path.display(), e);
+                    }
+                });
+                Some(path)
+            }
+            None => {
+                // the files went missing under us, forget about them
+                if let Some(entry) = index.entries.remove(&key) {
+                    index.total_size -= entry.size;
+                }
+                None
+            }
+        }
+    }
+
+    /// Register a track that `yt-dlp` just downloaded to `audio_path`, next
+    /// to [`TrackCache::download_stem`], then evict old entries if needed.
+    /// Every download counts as a miss.
+    pub async fn insert(
+        &self,
+        url: &str,
+        format: &str,
+        audio_path: PathBuf,
+        track_info: &TrackInfo,
+    ) -> Result<PathBuf, String> {
+        let key = Self::key(url, format);
+        let audio_size = tokio::fs::metadata(&audio_path)
+            .await
+            .map_err(|e| {
+                format!(
+                    "TrackCache::insert: the downloaded file {} doesn't exist: {}",
+                    audio_path.display(),
+                    e
+                )
+            })?
+            .len();
+
+        // expiring direct URLs are useless once the track is on disk
+        let mut track_info = track_info.clone();
+        track_info.strip_formats();
+        let sidecar = serde_json::to_string(&track_info)
+            .map_err(|e| format!("TrackCache::insert: can't serialize track info: {}", e))?;
+        tokio::fs::write(self.sidecar_path(&key), &sidecar)
+            .await
+            .map_err(|e| format!("TrackCache::insert: can't write sidecar: {}", e))?;
+        let size = audio_size + sidecar.len() as u64;
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let mut index = self.index.lock().await;
+        let last_used = index.tick();
+        if let Some(old) = index.entries.insert(
+            key.clone(),
+            CacheEntry {
+                audio_path: audio_path.clone(),
+                size,
+                last_used,
+            },
+        ) {
+            index.total_size -= old.size;
+        }
+        index.total_size += size;
+
+        // evict, never the entry we just added
+        while index.total_size > self.max_size && index.entries.len() > 1 {
+            let lru_key = match index
+                .entries
+                .iter()
+                .filter(|(entry_key, _)| **entry_key != key)
+                .min_by_key(|(_, entry)| entry.last_used)
+            {
+                Some((lru_key, _)) => lru_key.clone(),
+                None => break,
+            };
+            if let Some(evicted) = index.entries.remove(&lru_key) {
+                index.total_size -= evicted.size;
+                for path in [evicted.audio_path, self.sidecar_path(&lru_key)] {
+                    if let Err(e) = tokio::fs::remove_file(&path).await {
+                        warn!("can't remove evicted file {}: {}", path.display(), e);
+                    }
+                }
+            }
+        }
+
+        Ok(audio_path)
+    }
+
+    pub async fn stats(&self) -> CacheStats {
+        let index = self.index.lock().await;
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: index.entries.len(),
+            total_size: index.total_size,
+            max_size: self.max_size,
+        }
+    }
+}
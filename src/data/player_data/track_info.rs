@@ -1,21 +1,148 @@
-use std::path::PathBuf;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-use poise::serenity_prelude::{ChannelId, GuildId};
+use super::{FormatPolicy, ResolverKind};
+use crate::data::title_rules::TitleRules;
+
+use poise::serenity_prelude::{ChannelId, UserId};
+use tokio::sync::watch;
 use uuid::Uuid;
 
+/// Playable direct URL of a track and when it was resolved. Shared by all
+/// clones of a [`TrackInfo`], so that a prefetched URL reaches the queue.
+#[derive(Debug, Clone, Default)]
+pub struct StreamUrl(Arc<Mutex<Option<(String, Instant)>>>);
+
+impl StreamUrl {
+    /// Get the URL if it was resolved less than `max_age` ago.
+    pub fn get_fresh(&self, max_age: Duration) -> Option<String> {
+        match &*self.0.lock().ok()? {
+            Some((url, resolved_at)) if resolved_at.elapsed() < max_age => Some(url.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn set(&self, url: String) {
+        if let Ok(mut slot) = self.0.lock() {
+            *slot = Some((url, Instant::now()));
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut slot) = self.0.lock() {
+            *slot = None;
+        }
+    }
+}
+
+/// Song a radio is playing, read from its ICY metadata. Shared by all clones
+/// of a [`TrackInfo`], so that whoever shows the track can follow changes.
+#[derive(Debug, Clone)]
+pub struct StreamTitle(Arc<watch::Sender<Option<String>>>);
+
+impl Default for StreamTitle {
+    fn default() -> Self {
+        Self(Arc::new(watch::channel(None).0))
+    }
+}
+
+impl StreamTitle {
+    pub fn get(&self) -> Option<String> {
+        self.0.borrow().clone()
+    }
+
+    /// Change the title, subscribers only hear about actual changes.
+    pub fn set(&self, title: Option<String>) {
+        self.0.send_if_modified(|current| match *current == title {
+            true => false,
+            false => {
+                *current = title;
+                true
+            }
+        });
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Option<String>> {
+        self.0.subscribe()
+    }
+}
+
+/// Who asked for a track, with the name they had back then.
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub struct Requester {
+    pub id: UserId,
+    pub name: String,
+}
+
 /// Stores info about formats in a track.
-#[derive(Debug, serde::Deserialize, Clone)]
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct Format {
     pub url: String,
     #[serde(rename = "acodec")]
     pub codec: Option<String>,
     #[serde(rename = "abr")]
     pub bitrate: Option<f32>,
+    #[serde(rename = "vcodec")]
+    pub video_codec: Option<String>,
+    /// Bitrate of audio and video combined, for muxed formats.
+    #[serde(rename = "tbr")]
+    pub total_bitrate: Option<f32>,
+    pub protocol: Option<String>,
+    #[serde(default)]
+    pub format_id: Option<String>,
+    /// File extension, like `webm` or `m4a`.
+    #[serde(default)]
+    pub ext: Option<String>,
+    /// Set for DASH formats, like `webm_dash`.
+    #[serde(default)]
+    pub container: Option<String>,
+    /// Size in bytes, when known in advance.
+    #[serde(default)]
+    pub filesize: Option<u64>,
+}
+
+impl Format {
+    /// Something to tell the format apart in logs.
+    pub fn name(&self) -> String {
+        format!(
+            "{} ({} {}kbps in {} over {})",
+            self.format_id.as_deref().unwrap_or("?"),
+            self.codec.as_deref().unwrap_or("?"),
+            self.bitrate.map(|bitrate| bitrate.round()).unwrap_or(0.0),
+            self.container_name(),
+            self.protocol.as_deref().unwrap_or("?"),
+        )
+    }
+
+    /// The container, without yt-dlp's `_dash` suffix, or the extension.
+    pub fn container_name(&self) -> String {
+        self.container
+            .as_deref()
+            .map(|container| container.trim_end_matches("_dash"))
+            .or(self.ext.as_deref())
+            .unwrap_or_default()
+            .to_lowercase()
+    }
+
+    pub fn is_audio_only(&self) -> bool {
+        matches!(self.video_codec.as_deref(), None | Some("none"))
+    }
+
+    /// The audio bitrate in kbps, worked out from the file size when yt-dlp
+    /// doesn't say.
+    pub fn estimated_bitrate(&self, duration_in_sec: Option<u64>) -> Option<f32> {
+        self.bitrate.or_else(|| {
+            let duration_in_sec = duration_in_sec.filter(|duration| *duration > 0)?;
+            Some(self.filesize? as f32 * 8.0 / 1000.0 / duration_in_sec as f32)
+        })
+    }
 }
 
 /// Stores info about a track. This one exists because songbird's queue only
 /// stores track IDs and not allowed adding additional info to the queue.
-#[derive(Debug, serde::Deserialize, Clone)]
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct TrackInfo {
     // Functional fields
     #[serde(skip)]
@@ -23,17 +150,45 @@ pub struct TrackInfo {
     #[serde(rename = "original_url")]
     pub url: String,
     formats: Option<Vec<Format>>,
+    /// Resolved from `formats` right before playing, since they expire.
+    #[serde(skip)]
+    pub stream_url: StreamUrl,
+    /// Which resolver found the track, and prepares it for playing.
+    #[serde(default)]
+    pub resolver: ResolverKind,
+    /// What a radio is playing right now.
+    #[serde(skip)]
+    pub stream_title: StreamTitle,
+    /// How many times the track failed to play.
+    #[serde(skip)]
+    pub failures: u32,
+    /// Where playback picks up once the track starts, when it's played
+    /// again after a failure.
+    #[serde(skip)]
+    pub resume_at: Option<Duration>,
+
+    /// Set by yt-dlp for livestreams, and by resolvers of other endless
+    /// streams. A missing duration alone doesn't make a track live.
+    #[serde(default)]
+    pub is_live: Option<bool>,
 
     // Cosmetic fields
     #[serde(rename = "duration")]
-    pub duration_in_sec: u64,
+    pub duration_in_sec: Option<u64>,
     title: String,
     pub thumbnail: Option<String>,
     pub artist: Option<String>,
     pub uploader: Option<String>,
+    /// Page of the channel or uploader, set by yt-dlp.
+    #[serde(default)]
+    pub channel_url: Option<String>,
+    #[serde(default)]
+    pub uploader_url: Option<String>,
 
     /// Where the app was called from to send "Now playing" message.
     pub text_channel_id: Option<ChannelId>,
+    /// Who asked for the track.
+    pub requester: Option<Requester>,
 }
 
 impl Default for TrackInfo {
@@ -42,49 +197,62 @@ impl Default for TrackInfo {
             id: Uuid::new_v4(),
             url: "".to_string(),
             formats: None,
+            stream_url: StreamUrl::default(),
+            resolver: ResolverKind::default(),
+            stream_title: StreamTitle::default(),
+            failures: 0,
+            resume_at: None,
+            is_live: None,
 
-            duration_in_sec: 0,
+            duration_in_sec: None,
             title: "".to_string(),
             thumbnail: None,
             artist: None,
             uploader: None,
+            channel_url: None,
+            uploader_url: None,
 
             text_channel_id: None,
+            requester: None,
         }
     }
 }
 
+/// Format a duration in seconds as `HH:MM:SS`.
+pub fn pretty_duration(duration_in_sec: u64) -> String {
+    let hours = duration_in_sec / 3600;
+    let minutes = (duration_in_sec % 3600) / 60;
+    let seconds = duration_in_sec % 60;
+    format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+}
+
 impl TrackInfo {
-    /// Get a cleaned up title.
-    pub fn get_title(&self) -> String {
-        // use https://regexr.com/ to build the regex, using these test cases,
-        // space prefix included; remember to add them here if you find a new one
-        //
-        //  (official)
-        //  (official mv)
-        //  | official mv
-        //  (official audio)
-        //  [official audio]
-        //  (official video)
-        //  [official video]
-        //  (official music video)
-        //  [official music video]
-        //  | official music video
-        //  (official lyric video)
-        //  | official lyric video
-        //  [official lyric video]
-        //  | lyrics music video
-        //  | lyrics video
-        let re = regex::Regex::new(
-            r"(?im) (\(|\[|\| )( ?(official|mv|audio|video|music|lyrics|lyric) ?)+(\)|\]|)?",
-        );
-
-        if let Ok(re) = re {
-            return re.replace_all(&self.title, "").to_string();
+    /// A track with only a source URL and a title, for resolvers other than
+    /// `yt-dlp` to fill in.
+    pub fn new(resolver: ResolverKind, url: String, title: String) -> Self {
+        Self {
+            resolver,
+            url,
+            title,
+            ..Default::default()
         }
+    }
+
+    /// Get the title, cleaned up by [`Self::apply_title_rules`] if the
+    /// track went through them.
+    pub fn get_title(&self) -> String {
         self.title.clone()
     }
 
+    /// Clean the title up, and take the artist out of it if it's unknown.
+    pub fn apply_title_rules(&mut self, title_rules: &TitleRules) {
+        let clean_title = title_rules.clean(&self.title, self.artist.is_some());
+        self.title = clean_title.title;
+        if clean_title.artist.is_some() {
+            self.artist = clean_title.artist;
+        }
+    }
+
     /// Get description for Discord embed.
     pub fn get_pretty_description(&self) -> String {
         let author = self
@@ -93,82 +261,83 @@ impl TrackInfo {
             .or_else(|| self.uploader.clone())
             .unwrap_or("Unknown".to_string());
 
-        let duration = {
-            let duration_in_sec = self.duration_in_sec;
-            let hours = duration_in_sec / 3600;
-            let minutes = (duration_in_sec % 3600) / 60;
-            let seconds = duration_in_sec % 60;
-            format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+        let duration = match self.duration_in_sec {
+            _ if self.is_live() => "🔴 LIVE".to_string(),
+            Some(duration_in_sec) => pretty_duration(duration_in_sec),
+            None => "Unknown length".to_string(),
         };
 
         format!("{} | {}", author, duration)
     }
 
-    /// Get playable direct URL of the track from Vec<Format>.
-    /// If the track is not available in any of the formats, return `None`.
-    pub fn get_playable_url(&self) -> Option<String> {
-        let formats = self.formats.as_ref()?;
+    /// Who asked for the track, for embeds.
+    pub fn requester_name(&self) -> Option<&str> {
+        self.requester
+            .as_ref()
+            .map(|requester| requester.name.as_str())
+    }
 
-        let mut best_url = None;
-        let mut best_bitrate = 0.0;
+    /// Livestreams and radios, which have no end and can't be seeked.
+    pub fn is_live(&self) -> bool {
+        self.is_live.unwrap_or(false)
+    }
 
-        let mut mp3_url = None;
-        let mut mp3_bitrate = 0.0;
+    /// Get playable direct URL of the track from Vec<Format>, following
+    /// the format policy. If none of the formats can be streamed, return
+    /// `None`.
+    pub fn get_playable_url(&self, format_policy: &FormatPolicy) -> Option<String> {
+        if self.is_live() {
+            return self.get_live_url();
+        }
 
-        formats
+        format_policy
+            .pick(self.formats.as_ref()?, self.duration_in_sec, &self.url)
+            .map(|format| format.url.clone())
+    }
+
+    /// Get the URL of a live format, usually a HLS playlist or an Icecast
+    /// stream, to be played through ffmpeg. Prefers audio-only formats with
+    /// the best audio bitrate, then the lightest ones.
+    fn get_live_url(&self) -> Option<String> {
+        self.formats
+            .as_ref()?
             .iter()
-            .filter_map(|format| match (format.codec.as_deref(), format.bitrate) {
-                (Some(codec), Some(bitrate)) => Some((codec, bitrate, &format.url)),
-                _ => None,
-            })
-            .for_each(|(codec, bitrate, url)| match codec {
-                "alac" | "flac" | "pcm" => {
-                    best_url = Some(url.clone());
-                    best_bitrate = bitrate;
-                }
-                "opus" | "aac" | "vorbis" => {
-                    if bitrate > best_bitrate {
-                        best_url = Some(url.clone());
-                        best_bitrate = bitrate;
-                    }
-                }
-                // final resort if can't find a better one
-                "mp3" => {
-                    if bitrate > mp3_bitrate {
-                        mp3_url = Some(url.clone());
-                        mp3_bitrate = bitrate;
-                    }
-                }
-                _ => (),
-            });
-
-        best_url.or(mp3_url)
-    }
-
-    /// Get the output path for `yt-dlp` to download the track.
-    pub fn get_download_path(&self, guild_id: &GuildId) -> String {
-        format!("/tmp/taxer/{}/{}", guild_id, self.id)
-    }
-
-    /// Get the input path for songbird to play the track.
-    pub fn get_input_path(&self, guild_id: &GuildId) -> Result<PathBuf, String> {
-        std::fs::read_dir(format!("/tmp/taxer/{}", guild_id))
-            .map_err(|_| {
-                format!(
-                    "TrackInfo::get_input_path: can't read /tmp/taxer/{}",
-                    guild_id
-                )
-            })?
-            .filter_map(|entry| entry.ok())
-            .find(|file| {
-                file.file_name()
-                    .to_string_lossy()
-                    .contains(&self.id.to_string())
+            .filter(|format| format.codec.as_deref() != Some("none"))
+            .max_by(|a, b| {
+                let audio_only = |format: &Format| format.video_codec.as_deref() == Some("none");
+                audio_only(a)
+                    .cmp(&audio_only(b))
+                    .then(
+                        a.bitrate
+                            .unwrap_or(0.0)
+                            .total_cmp(&b.bitrate.unwrap_or(0.0)),
+                    )
+                    .then(
+                        b.total_bitrate
+                            .unwrap_or(0.0)
+                            .total_cmp(&a.total_bitrate.unwrap_or(0.0)),
+                    )
             })
-            .map(|file| file.path())
-            .ok_or(format!(
-                "TrackInfo::get_input_path: the track file doesn't exist: {}",
-                self.id
-            ))
+            .map(|format| format.url.clone())
+    }
+
+    /// Drop the direct URLs of the track, for when they're not needed anymore.
+    pub fn strip_formats(&mut self) {
+        self.formats = None;
+    }
+
+    /// Pick a playable URL out of the formats into [`TrackInfo::stream_url`],
+    /// then drop the formats so only the source URL and metadata are kept.
+    /// Returns whether a playable URL was found.
+    pub fn prime_stream_url(&mut self, format_policy: &FormatPolicy) -> bool {
+        let playable_url = self.get_playable_url(format_policy);
+        self.strip_formats();
+        match playable_url {
+            Some(url) => {
+                self.stream_url.set(url);
+                true
+            }
+            None => false,
+        }
     }
 }
@@ -0,0 +1,466 @@
+use super::TrackInfo;
+use crate::data::config::Config;
+
+use std::{
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, BufReader, Lines},
+    process::{Child, ChildStderr, ChildStdout, Command},
+    sync::{watch, OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle,
+};
+use tracing::{error, warn};
+
+/// How much of what `yt-dlp` prints on stderr is kept to explain failures.
+const MAX_STDERR_LEN: usize = 16 * 1024;
+
+/// Told to users when `yt-dlp` can't make sense of a site anymore, which
+/// usually means it has to be updated.
+const EXTRACTOR_BROKEN: &str =
+    "`yt-dlp` can't read this site anymore, it probably needs an update.";
+
+/// Why `yt-dlp` failed, guessed from its error messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FailureKind {
+    Unavailable,
+    AgeRestricted,
+    GeoBlocked,
+    RateLimited,
+    ExtractorBroken,
+    /// The bot couldn't reach the site at all.
+    Network,
+    NotFound,
+    Other,
+}
+
+/// The `ERROR:` lines of what `yt-dlp` printed on stderr, or all of it when
+/// there are none.
+fn error_message(stderr: &str) -> String {
+    let errors = stderr
+        .lines()
+        .filter_map(|line| line.strip_prefix("ERROR:"))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    match errors.is_empty() {
+        true => stderr.trim().to_string(),
+        false => errors.join("\n"),
+    }
+}
+
+impl FailureKind {
+    /// Classify a failure from what `yt-dlp` printed on stderr. Broken
+    /// extractors are looked for in warnings too, the errors they lead to
+    /// don't tell. Some messages contain others, so the order of the checks
+    /// matters.
+    fn of(stderr: &str) -> Self {
+        let message = error_message(stderr).to_lowercase();
+        let stderr = stderr.to_lowercase();
+        let has_any = |patterns: &[&str]| patterns.iter().any(|p| message.contains(p));
+        if has_any(&[
+            "http error 429",
+            "too many requests",
+            "rate-limit",
+            "rate limit",
+            "not a bot",
+        ]) {
+            Self::RateLimited
+        } else if has_any(&[
+            "confirm your age",
+            "age-restricted",
+            "age restricted",
+            "inappropriate for some users",
+        ]) {
+            Self::AgeRestricted
+        } else if has_any(&[
+            "in your country",
+            "geo restrict",
+            "geo-restrict",
+            "not available from your location",
+        ]) {
+            Self::GeoBlocked
+        } else if has_any(&[
+            "name or service not known",
+            "getaddrinfo failed",
+            "temporary failure in name resolution",
+            "nodename nor servname",
+            "network is unreachable",
+            "connection refused",
+            "connection reset",
+        ]) {
+            Self::Network
+        } else if has_any(&[
+            "unsupported url",
+            "is not a valid url",
+            "http error 404",
+            "no video results",
+        ]) {
+            Self::NotFound
+        } else if [
+            "unable to extract",
+            "failed to extract",
+            "nsig extraction failed",
+            "signature extraction failed",
+            "please report this issue",
+            "requested format is not available",
+        ]
+        .iter()
+        .any(|p| stderr.contains(p))
+        {
+            Self::ExtractorBroken
+        } else if has_any(&[
+            "video unavailable",
+            "private video",
+            "has been removed",
+            "account associated with this video has been terminated",
+            "members-only",
+            "members only",
+            "this live event will begin",
+            "premieres in",
+            "is not available",
+        ]) {
+            Self::Unavailable
+        } else {
+            Self::Other
+        }
+    }
+
+    fn explanation(self) -> Option<&'static str> {
+        match self {
+            Self::Unavailable => Some("This video is unavailable, it may be private or removed."),
+            Self::AgeRestricted => Some("This video is age-restricted, it can't be played."),
+            Self::GeoBlocked => Some("This video isn't available in the bot's country."),
+            Self::RateLimited => {
+                Some("The site is rate-limiting the bot, try again in a few minutes.")
+            }
+            Self::ExtractorBroken => Some(EXTRACTOR_BROKEN),
+            Self::Network => Some("Couldn't reach the site, it or the bot's network may be down."),
+            Self::NotFound => Some("Nothing found there, check the link or the search."),
+            Self::Other => None,
+        }
+    }
+}
+
+/// Turn what a failed `yt-dlp` printed into something users can read, and
+/// report sites it can't read anymore through `breakages`.
+fn explain_failure(
+    status: ExitStatus,
+    stderr: &str,
+    breakages: &watch::Sender<Option<String>>,
+) -> String {
+    // warnings are noisy and rarely why yt-dlp failed
+    let message = error_message(stderr);
+    warn!("yt-dlp exited with {}: {}", status, message);
+
+    let kind = FailureKind::of(stderr);
+    if kind == FailureKind::ExtractorBroken {
+        breakages.send_replace(Some(stderr.trim().to_string()));
+    }
+    match kind.explanation() {
+        Some(explanation) => explanation.to_string(),
+        None => match message.lines().last() {
+            Some(error) => format!("`yt-dlp` failed: {}", error),
+            None => format!("`yt-dlp` exited with {}", status),
+        },
+    }
+}
+
+/// Read what a `yt-dlp` process prints on stderr in the background, so
+/// that it never blocks on a full pipe.
+fn collect_stderr(stderr: Option<ChildStderr>) -> JoinHandle<String> {
+    tokio::spawn(async move {
+        let mut output = String::new();
+        let Some(stderr) = stderr else {
+            return output;
+        };
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if output.len() + line.len() < MAX_STDERR_LEN {
+                output.push_str(&line);
+                output.push('\n');
+            }
+        }
+        output
+    })
+}
+
+/// Runs `yt-dlp` processes on the async runtime. The number of processes
+/// running at once is capped across all guilds, and every process is
+/// killed as soon as the future or [`Resolution`] owning it is dropped.
+#[derive(Debug)]
+pub struct YtDlp {
+    yt_dlp_path: String,
+    ffmpeg_path: String,
+    /// For listing what queries point to, which can take a while for
+    /// big playlists.
+    permits: Arc<Semaphore>,
+    /// For getting tracks ready to play, so that imports never hold them up.
+    playback_permits: Arc<Semaphore>,
+    /// How long a resolution can stay silent before being killed.
+    resolve_timeout: Duration,
+    /// How long a single download can take before being killed.
+    download_timeout: Duration,
+    /// The last error of `yt-dlp` not being able to read a site, whatever
+    /// it was doing.
+    breakages: watch::Sender<Option<String>>,
+}
+
+impl YtDlp {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            yt_dlp_path: config.yt_dlp_path.clone(),
+            ffmpeg_path: config.ffmpeg_path.clone(),
+            permits: Arc::new(Semaphore::new(config.yt_dlp_max_concurrency)),
+            playback_permits: Arc::new(Semaphore::new(config.yt_dlp_max_concurrency)),
+            resolve_timeout: config.yt_dlp_resolve_timeout,
+            download_timeout: config.yt_dlp_download_timeout,
+            breakages: watch::Sender::new(None),
+        }
+    }
+
+    /// Get told whenever `yt-dlp` can't read a site anymore, with its error.
+    pub fn breakages(&self) -> watch::Receiver<Option<String>> {
+        self.breakages.subscribe()
+    }
+
+    async fn acquire(permits: &Arc<Semaphore>) -> Result<OwnedSemaphorePermit, String> {
+        permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| format!("YtDlp: can't acquire a process slot: {}", e))
+    }
+
+    /// Start resolving an URL or a search query into tracks.
+    pub async fn resolve(&self, query: &str) -> Result<Resolution, String> {
+        let permit = Self::acquire(&self.permits).await?;
+        self.spawn_resolution(query, &[], permit)
+    }
+
+    /// Resolve a single track from its source URL, ignoring any playlist
+    /// it might be part of. Used to get fresh direct URLs before playing.
+    pub async fn resolve_single(&self, url: &str) -> Result<TrackInfo, String> {
+        let permit = Self::acquire(&self.playback_permits).await?;
+        let mut resolution = self.spawn_resolution(url, &["--no-playlist"], permit)?;
+        match resolution.next_track().await? {
+            Some(track_info) => Ok(track_info),
+            // yt-dlp knows why it has nothing
+            None => resolution
+                .wait()
+                .await
+                .and(Err(format!("yt-dlp returned nothing for {}", url))),
+        }
+    }
+
+    fn spawn_resolution(
+        &self,
+        query: &str,
+        extra_args: &[&str],
+        permit: OwnedSemaphorePermit,
+    ) -> Result<Resolution, String> {
+        let mut child = Command::new(&self.yt_dlp_path)
+            .arg("-x")
+            .arg("--default-search")
+            .arg("ytsearch")
+            .arg("--skip-download")
+            .arg("--print-json")
+            .args(extra_args)
+            // so that queries are never taken for options
+            .arg("--")
+            .arg(query)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("can't run yt-dlp: {}", e))?;
+        let stderr = collect_stderr(child.stderr.take());
+
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or("can't read yt-dlp output".to_string())?;
+
+        Ok(Resolution {
+            child,
+            lines: BufReader::new(stdout).lines(),
+            stderr,
+            idle_timeout: self.resolve_timeout,
+            permit: Some(permit),
+            breakages: self.breakages.clone(),
+        })
+    }
+
+    /// Download and convert the track at `url` into `<output_stem>.<ext>`,
+    /// returns the path of the file.
+    pub async fn download(
+        &self,
+        url: &str,
+        output_stem: &Path,
+        format: &str,
+    ) -> Result<PathBuf, String> {
+        let _permit = Self::acquire(&self.playback_permits).await?;
+
+        let mut child = Command::new(&self.yt_dlp_path)
+            .arg("--ffmpeg-location")
+            .arg(&self.ffmpeg_path)
+            .arg("--audio-format")
+            .arg(format)
+            .arg("--audio-quality")
+            .arg("0")
+            .arg("-x")
+            .arg("-o")
+            .arg(format!("{}.%(ext)s", output_stem.display()))
+            // the extension is only known once converted
+            .arg("--print")
+            .arg("after_move:filepath")
+            .arg("--")
+            .arg(url)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("can't spawn yt-dlp process to download track: {}", e))?;
+        let mut stdout =
+            BufReader::new(child.stdout.take().ok_or("no stdout from yt-dlp")?).lines();
+        let stderr = collect_stderr(child.stderr.take());
+
+        let downloading = async {
+            let mut path = None;
+            while let Ok(Some(line)) = stdout.next_line().await {
+                if !line.trim().is_empty() {
+                    path = Some(PathBuf::from(line.trim()));
+                }
+            }
+            (child.wait().await, path)
+        };
+        match tokio::time::timeout(self.download_timeout, downloading).await {
+            Ok((Ok(status), Some(path))) if status.success() => Ok(path),
+            Ok((Ok(status), None)) if status.success() => {
+                Err("yt-dlp didn't tell where it saved the track".to_string())
+            }
+            Ok((Ok(status), _)) => Err(explain_failure(
+                status,
+                &stderr.await.unwrap_or_default(),
+                &self.breakages,
+            )),
+            Ok((Err(e), _)) => Err(format!("can't wait for yt-dlp to finish: {}", e)),
+            Err(_) => Err(format!(
+                "yt-dlp took longer than {}s to download the track",
+                self.download_timeout.as_secs()
+            )),
+        }
+    }
+}
+
+/// A running `yt-dlp` process printing one JSON object per track.
+#[derive(Debug)]
+pub struct Resolution {
+    child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+    stderr: JoinHandle<String>,
+    idle_timeout: Duration,
+    /// Given back once the output is all read, `yt-dlp` only has to exit.
+    permit: Option<OwnedSemaphorePermit>,
+    breakages: watch::Sender<Option<String>>,
+}
+
+impl Resolution {
+    /// Get the next resolved track, `None` once `yt-dlp` is done.
+    pub async fn next_track(&mut self) -> Result<Option<TrackInfo>, String> {
+        loop {
+            let line = match tokio::time::timeout(self.idle_timeout, self.lines.next_line()).await {
+                Ok(Ok(Some(line))) => line,
+                Ok(Ok(None)) => {
+                    self.permit = None;
+                    return Ok(None);
+                }
+                Ok(Err(e)) => return Err(format!("can't read yt-dlp output: {}", e)),
+                Err(_) => {
+                    return Err(format!(
+                        "yt-dlp didn't answer for {}s, giving up",
+                        self.idle_timeout.as_secs()
+                    ))
+                }
+            };
+
+            match serde_json::from_str(line.as_str()) {
+                Ok(track_info) => return Ok(Some(track_info)),
+                Err(e) => error!("can't parse yt-dlp output: {}", e),
+            }
+        }
+    }
+
+    /// Wait for `yt-dlp` to exit after its output has been consumed, and
+    /// explain why it failed if it did.
+    pub async fn wait(mut self) -> Result<(), String> {
+        let status = self
+            .child
+            .wait()
+            .await
+            .map_err(|e| format!("can't wait for yt-dlp to finish: {}", e))?;
+        if status.success() {
+            return Ok(());
+        }
+        Err(explain_failure(
+            status,
+            &self.stderr.await.unwrap_or_default(),
+            &self.breakages,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_failures() {
+        let cases = [
+            ("ERROR: [youtube] abc: Video unavailable", FailureKind::Unavailable),
+            ("ERROR: [youtube] abc: Private video. Sign in", FailureKind::Unavailable),
+            (
+                "ERROR: [youtube] abc: Sign in to confirm your age",
+                FailureKind::AgeRestricted,
+            ),
+            (
+                "ERROR: [youtube] abc: The uploader has not made this video available in your country",
+                FailureKind::GeoBlocked,
+            ),
+            ("ERROR: unable to download: HTTP Error 429: Too Many Requests", FailureKind::RateLimited),
+            ("ERROR: [youtube] abc: Sign in to confirm you're not a bot", FailureKind::RateLimited),
+            (
+                "ERROR: [youtube] abc: Unable to extract uploader id; please report this issue",
+                FailureKind::ExtractorBroken,
+            ),
+            (
+                "ERROR: [generic] Unable to download webpage: <urlopen error [Errno -2] Name or service not known>",
+                FailureKind::Network,
+            ),
+            ("ERROR: Unsupported URL: https://example.com", FailureKind::NotFound),
+            ("ERROR: [generic] abc: HTTP Error 404: Not Found", FailureKind::NotFound),
+            (
+                "ERROR: [youtube] abc: Requested format is not available. Use --list-formats",
+                FailureKind::ExtractorBroken,
+            ),
+            (
+                "WARNING: [youtube] abc: nsig extraction failed: Some formats may be missing\n\
+                ERROR: [youtube] abc: This video is not available",
+                FailureKind::ExtractorBroken,
+            ),
+            (
+                "WARNING: [youtube] Falling back to generic n function search\n\
+                ERROR: [youtube] abc: Video unavailable",
+                FailureKind::Unavailable,
+            ),
+            ("ERROR: something else entirely", FailureKind::Other),
+            ("something else entirely", FailureKind::Other),
+        ];
+        for (message, kind) in cases {
+            assert_eq!(FailureKind::of(message), kind, "{}", message);
+        }
+    }
+}
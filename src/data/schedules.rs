@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+
+use poise::serenity_prelude::{ChannelId, GuildId, UserId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, RwLock};
+use tracing::warn;
+
+/// How many jobs a guild can have waiting.
+pub const MAX_JOBS_PER_GUILD: usize = 10;
+
+/// Playback to start at a set time, added with /schedule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledJob {
+    /// Shown in /schedule list, to cancel the job.
+    pub id: u32,
+    pub guild_id: GuildId,
+    /// Where the bot joins to play.
+    pub voice_channel_id: ChannelId,
+    /// Where /schedule was used, the job is reported there.
+    pub text_channel_id: ChannelId,
+    pub user_id: UserId,
+    /// The name of the user in the guild, when they scheduled the job.
+    #[serde(default)]
+    pub user_name: String,
+    pub query: String,
+    /// Unix timestamp, in seconds.
+    pub starts_at: u64,
+}
+
+/// Every scheduled job, kept in a JSON file so that they survive restarts.
+#[derive(Debug)]
+pub struct ScheduleStore {
+    path: PathBuf,
+    jobs: RwLock<Vec<ScheduledJob>>,
+    /// Wakes the scheduler up when jobs are added or cancelled.
+    pub changed: Notify,
+}
+
+impl ScheduleStore {
+    pub fn new(data_dir: &str) -> Self {
+        let path = Path::new(data_dir).join("schedules.json");
+        let jobs = super::load_json(&path);
+        Self {
+            path,
+            jobs: RwLock::new(jobs),
+            changed: Notify::new(),
+        }
+    }
+
+    async fn save(&self, jobs: &[ScheduledJob]) {
+        if let Err(e) = super::save_json(&self.path, jobs).await {
+            warn!("can't save scheduled jobs: {}", e);
+        }
+    }
+
+    /// Add a job, giving it an ID. Fails when the guild has too many.
+    pub async fn add(&self, mut job: ScheduledJob) -> Result<ScheduledJob, String> {
+        let mut jobs = self.jobs.write().await;
+        if jobs.iter().filter(|j| j.guild_id == job.guild_id).count() >= MAX_JOBS_PER_GUILD {
+            return Err(format!(
+                "This guild already has `{}` scheduled jobs, cancel some first!",
+                MAX_JOBS_PER_GUILD
+            ));
+        }
+        job.id = jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        jobs.push(job.clone());
+        self.save(&jobs).await;
+        self.changed.notify_one();
+        Ok(job)
+    }
+
+    /// The jobs of a guild, the soonest first.
+    pub async fn of_guild(&self, guild_id: GuildId) -> Vec<ScheduledJob> {
+        let mut jobs = self
+            .jobs
+            .read()
+            .await
+            .iter()
+            .filter(|job| job.guild_id == guild_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        jobs.sort_by_key(|job| job.starts_at);
+        jobs
+    }
+
+    /// Remove a job of a guild, if `allowed` lets whoever asks do it.
+    pub async fn cancel(
+        &self,
+        guild_id: GuildId,
+        id: u32,
+        allowed: impl FnOnce(&ScheduledJob) -> bool,
+    ) -> Result<ScheduledJob, String> {
+        let mut jobs = self.jobs.write().await;
+        let position = jobs
+            .iter()
+            .position(|job| job.guild_id == guild_id && job.id == id)
+            .ok_or(format!("There's no scheduled job `#{}`!", id))?;
+        if !allowed(&jobs[position]) {
+            return Err(format!(
+                "Only <@{}> or server managers can cancel this job!",
+                jobs[position].user_id
+            ));
+        }
+        let job = jobs.remove(position);
+        self.save(&jobs).await;
+        self.changed.notify_one();
+        Ok(job)
+    }
+
+    /// Remove and return the jobs due at `now`.
+    pub async fn take_due(&self, now: u64) -> Vec<ScheduledJob> {
+        let mut jobs = self.jobs.write().await;
+        let (due, waiting) = std::mem::take(&mut *jobs)
+            .into_iter()
+            .partition::<Vec<_>, _>(|job| job.starts_at <= now);
+        *jobs = waiting;
+        if !due.is_empty() {
+            self.save(&jobs).await;
+        }
+        due
+    }
+
+    /// When the next job starts.
+    pub async fn next_start(&self) -> Option<u64> {
+        self.jobs.read().await.iter().map(|job| job.starts_at).min()
+    }
+}
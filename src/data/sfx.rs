@@ -0,0 +1,112 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use poise::serenity_prelude::GuildId;
+use tracing::warn;
+
+/// Extensions clips can have, all of them decodable by symphonia.
+pub const CLIP_EXTENSIONS: [&str; 5] = ["mp3", "ogg", "opus", "wav", "flac"];
+
+/// Whether a clip name is safe to use as a file name.
+pub fn is_valid_clip_name(name: &str) -> bool {
+    (1..=32).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// Clips of the soundboard: the ones shared by every guild in `SFX_DIR`,
+/// and the ones admins uploaded to their guild, in the data directory.
+#[derive(Debug)]
+pub struct SfxStore {
+    global_dir: Option<PathBuf>,
+    guilds_dir: PathBuf,
+}
+
+impl SfxStore {
+    pub fn new(global_dir: Option<&str>, data_dir: &str) -> Self {
+        Self {
+            global_dir: global_dir.map(PathBuf::from),
+            guilds_dir: Path::new(data_dir).join("sfx"),
+        }
+    }
+
+    fn guild_dir(&self, guild_id: GuildId) -> PathBuf {
+        self.guilds_dir.join(guild_id.to_string())
+    }
+
+    /// The clips of a directory, by name.
+    async fn clips_in(dir: &Path) -> BTreeMap<String, PathBuf> {
+        let mut clips = BTreeMap::new();
+        let mut entries = match tokio::fs::read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return clips,
+            Err(e) => {
+                warn!("can't read {}: {}", dir.display(), e);
+                return clips;
+            }
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let (Some(name), Some(extension)) = (
+                path.file_stem().and_then(|stem| stem.to_str()),
+                path.extension().and_then(|extension| extension.to_str()),
+            ) else {
+                continue;
+            };
+            if is_valid_clip_name(name) && CLIP_EXTENSIONS.contains(&extension) {
+                clips.insert(name.to_string(), path.clone());
+            }
+        }
+        clips
+    }
+
+    /// Every clip a guild can play, its own ones taking over shared ones
+    /// with the same name.
+    pub async fn clips(&self, guild_id: GuildId) -> BTreeMap<String, PathBuf> {
+        let mut clips = match &self.global_dir {
+            Some(global_dir) => Self::clips_in(global_dir).await,
+            None => BTreeMap::new(),
+        };
+        clips.extend(Self::clips_in(&self.guild_dir(guild_id)).await);
+        clips
+    }
+
+    /// The clips uploaded to a guild, by name.
+    pub async fn guild_clips(&self, guild_id: GuildId) -> BTreeMap<String, PathBuf> {
+        Self::clips_in(&self.guild_dir(guild_id)).await
+    }
+
+    /// Save a clip to a guild, replacing the one with the same name.
+    pub async fn add(
+        &self,
+        guild_id: GuildId,
+        name: &str,
+        extension: &str,
+        content: &[u8],
+    ) -> Result<(), String> {
+        let guild_dir = self.guild_dir(guild_id);
+        tokio::fs::create_dir_all(&guild_dir)
+            .await
+            .map_err(|e| format!("can't create {}: {}", guild_dir.display(), e))?;
+        self.remove(guild_id, name).await?;
+
+        let path = guild_dir.join(format!("{}.{}", name, extension));
+        tokio::fs::write(&path, content)
+            .await
+            .map_err(|e| format!("can't write {}: {}", path.display(), e))
+    }
+
+    /// Delete a clip uploaded to a guild, returns whether there was one.
+    pub async fn remove(&self, guild_id: GuildId, name: &str) -> Result<bool, String> {
+        match self.guild_clips(guild_id).await.get(name) {
+            Some(path) => tokio::fs::remove_file(path)
+                .await
+                .map(|_| true)
+                .map_err(|e| format!("can't delete {}: {}", path.display(), e)),
+            None => Ok(false),
+        }
+    }
+}
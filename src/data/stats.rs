@@ -0,0 +1,222 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use poise::serenity_prelude::{GuildId, UserId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, RwLock};
+use tracing::warn;
+
+/// How many tracks a guild keeps stats of, the least played ones go first.
+const MAX_TRACKS_PER_GUILD: usize = 1000;
+/// How many tracks a user keeps stats of.
+const MAX_TRACKS_PER_USER: usize = 100;
+/// Tracks stopped earlier than this before their end count as skipped.
+const SKIP_MARGIN_IN_SEC: u64 = 5;
+/// How often the stats get saved, if they changed.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often a track played in a guild.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackStats {
+    pub title: String,
+    pub plays: u64,
+}
+
+/// What played and how it ended, overall or for a single requester.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayStats {
+    pub plays: u64,
+    /// Tracks that ended, skipped or not, to get a skip rate.
+    pub ended: u64,
+    pub skipped: u64,
+    /// Time spent playing, pauses excluded.
+    pub listened_in_sec: u64,
+    /// Tracks by source URL.
+    pub tracks: HashMap<String, TrackStats>,
+}
+
+impl PlayStats {
+    fn record_play(&mut self, url: &str, title: &str, max_tracks: usize) {
+        self.plays += 1;
+        let track = self.tracks.entry(url.to_string()).or_default();
+        track.title = title.to_string();
+        track.plays += 1;
+
+        if self.tracks.len() > max_tracks {
+            let least_played = self
+                .tracks
+                .iter()
+                .filter(|(track_url, _)| *track_url != url)
+                .min_by_key(|(_, track)| track.plays)
+                .map(|(track_url, _)| track_url.clone());
+            if let Some(least_played) = least_played {
+                self.tracks.remove(&least_played);
+            }
+        }
+    }
+
+    fn record_end(&mut self, listened_in_sec: u64, skipped: bool) {
+        self.ended += 1;
+        self.skipped += skipped as u64;
+        self.listened_in_sec += listened_in_sec;
+    }
+
+    /// Share of the ended tracks that got skipped, in percent.
+    pub fn skip_rate(&self) -> f64 {
+        match self.ended {
+            0 => 0.0,
+            ended => self.skipped as f64 * 100.0 / ended as f64,
+        }
+    }
+
+    /// The most played tracks with their URL, the most played first.
+    pub fn top_tracks(&self, count: usize) -> Vec<(&str, &TrackStats)> {
+        let mut tracks = self
+            .tracks
+            .iter()
+            .map(|(url, track)| (url.as_str(), track))
+            .collect::<Vec<_>>();
+        tracks.sort_by(|a, b| b.1.plays.cmp(&a.1.plays).then(a.1.title.cmp(&b.1.title)));
+        tracks.truncate(count);
+        tracks
+    }
+}
+
+/// Listening stats of a guild.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildStats {
+    #[serde(flatten)]
+    pub overall: PlayStats,
+    /// Tracks started by hour of the day, in UTC.
+    pub plays_by_hour: [u64; 24],
+    /// The same stats, restricted to the tracks each user requested.
+    pub requesters: HashMap<UserId, PlayStats>,
+}
+
+/// The listening stats of every guild, kept in memory and saved to a JSON
+/// file every [`FLUSH_INTERVAL`] and on shutdown.
+#[derive(Debug)]
+pub struct StatsStore {
+    path: PathBuf,
+    stats: RwLock<HashMap<GuildId, GuildStats>>,
+    /// Whether the stats changed since they were last saved.
+    dirty: AtomicBool,
+    /// Held while saving, so that saves never interleave.
+    saving: Mutex<()>,
+}
+
+impl StatsStore {
+    pub fn new(data_dir: &str) -> Self {
+        let path = Path::new(data_dir).join("stats.json");
+        let stats = super::load_json(&path);
+        Self {
+            path,
+            stats: RwLock::new(stats),
+            dirty: AtomicBool::new(false),
+            saving: Mutex::new(()),
+        }
+    }
+
+    /// Save the stats in the background every [`FLUSH_INTERVAL`].
+    pub fn start_flushing(self: &Arc<Self>) {
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                store.flush().await;
+            }
+        });
+    }
+
+    /// Save the stats if they changed since the last save.
+    pub async fn flush(&self) {
+        let _saving = self.saving.lock().await;
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return;
+        }
+
+        // a copy, so that tracks keep being counted while it's written
+        let stats = self.stats.read().await.clone();
+        if let Err(e) = super::save_json(&self.path, &stats).await {
+            warn!("can't save stats: {}", e);
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Get the stats of a guild, empty ones if nothing played there.
+    pub async fn get(&self, guild_id: GuildId) -> GuildStats {
+        self.stats
+            .read()
+            .await
+            .get(&guild_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    async fn update(&self, guild_id: GuildId, change: impl FnOnce(&mut GuildStats)) {
+        let mut stats = self.stats.write().await;
+        change(stats.entry(guild_id).or_default());
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Count a track that just started playing.
+    pub async fn record_play(
+        &self,
+        guild_id: GuildId,
+        url: &str,
+        title: &str,
+        requester: Option<UserId>,
+        started_at: u64,
+    ) {
+        self.update(guild_id, |guild_stats| {
+            guild_stats
+                .overall
+                .record_play(url, title, MAX_TRACKS_PER_GUILD);
+            guild_stats.plays_by_hour[(started_at / 3600 % 24) as usize] += 1;
+            if let Some(requester) = requester {
+                guild_stats
+                    .requesters
+                    .entry(requester)
+                    .or_default()
+                    .record_play(url, title, MAX_TRACKS_PER_USER);
+            }
+        })
+        .await;
+    }
+
+    /// Count a track that stopped after playing for `listened_in_sec`, at
+    /// `position_in_sec`. Tracks of unknown length are never skipped.
+    pub async fn record_end(
+        &self,
+        guild_id: GuildId,
+        requester: Option<UserId>,
+        listened_in_sec: u64,
+        position_in_sec: u64,
+        duration_in_sec: Option<u64>,
+    ) {
+        let skipped = duration_in_sec
+            .is_some_and(|duration_in_sec| position_in_sec + SKIP_MARGIN_IN_SEC < duration_in_sec);
+        self.update(guild_id, |guild_stats| {
+            guild_stats.overall.record_end(listened_in_sec, skipped);
+            if let Some(requester) = requester {
+                guild_stats
+                    .requesters
+                    .entry(requester)
+                    .or_default()
+                    .record_end(listened_in_sec, skipped);
+            }
+        })
+        .await;
+    }
+}
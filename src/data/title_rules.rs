@@ -0,0 +1,229 @@
+use super::guild_settings::GuildSettings;
+
+use poise::serenity_prelude::GuildId;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+/// A regex replaced in track titles. With `artist`, the rule also tells who
+/// the artist is, for tracks that don't say; both templates can use the
+/// regex's capture groups, like `$artist`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TitleRule {
+    pub pattern: String,
+    #[serde(default)]
+    pub replacement: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+}
+
+impl TitleRule {
+    fn new(pattern: &str, replacement: &str, artist: Option<&str>) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            artist: artist.map(str::to_string),
+        }
+    }
+}
+
+/// The rules used when neither `TITLE_RULES_PATH` nor the guild has any.
+pub fn default_rules() -> Vec<TitleRule> {
+    vec![
+        // 【MV】, 【Official Video】, 【歌ってみた】 and other tags
+        TitleRule::new(r"\s*【[^】]*】\s*", " ", None),
+        // [MV], (Official Audio), （公式）...
+        TitleRule::new(
+            r"(?i)\s*[(\[（［〔]\s*(official|mv|m/v|pv|audio|video|music|lyrics?|visualizer|公式|뮤직비디오|ミュージックビデオ)[^)\]）］〕]*[)\]）］〕]",
+            "",
+            None,
+        ),
+        // | Official Music Video, | Lyrics Video...
+        TitleRule::new(
+            r"(?i)\s*\|( ?(official|mv|audio|video|music|lyrics|lyric) ?)+$",
+            "",
+            None,
+        ),
+        // Official Music Video, without brackets
+        TitleRule::new(
+            r"(?i)\s*\bofficial\s+((music|lyric)\s+)?(video|audio)$",
+            "",
+            None,
+        ),
+        // 米津玄師 MV「Lemon」
+        TitleRule::new(r"(\s+(MV|PV|M/V))+\s*([「『])", "$3", None),
+        // YOASOBI「夜に駆ける」
+        TitleRule::new(
+            r"^(?P<artist>[^「『]+?)\s*[「『](?P<title>[^」』]+)[」』]\s*$",
+            "$title",
+            Some("$artist"),
+        ),
+        // Artist - Title, and Korean-style Artist _ Title
+        TitleRule::new(
+            r"^(?P<artist>.+?)\s+[-–—_]\s+(?P<title>.+)$",
+            "$title",
+            Some("$artist"),
+        ),
+    ]
+}
+
+/// A title after going through the rules.
+#[derive(Debug, Clone)]
+pub struct CleanTitle {
+    pub title: String,
+    /// Found by a rule, only when the track had no artist.
+    pub artist: Option<String>,
+}
+
+/// Compiled [`TitleRule`]s, applied in order.
+#[derive(Debug, Clone)]
+pub struct TitleRules(Vec<(Regex, TitleRule)>);
+
+impl TitleRules {
+    pub fn compile(rules: &[TitleRule]) -> Result<Self, String> {
+        rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| match Regex::new(&rule.pattern) {
+                Ok(regex) => Ok((regex, rule.clone())),
+                Err(e) => Err(format!("rule {} is not a valid regex: {}", index + 1, e)),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self)
+    }
+
+    /// Load the rules from the JSON file at `path`, or use the defaults.
+    pub fn load(path: Option<&str>) -> Self {
+        let rules = match path {
+            Some(path) => std::fs::read(path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| {
+                    serde_json::from_slice::<Vec<TitleRule>>(&content).map_err(|e| e.to_string())
+                })
+                .unwrap_or_else(|e| {
+                    error!("can't read title rules from {}: {}", path, e);
+                    std::process::exit(1);
+                }),
+            None => default_rules(),
+        };
+        Self::compile(&rules).unwrap_or_else(|e| {
+            error!("invalid title rules: {}", e);
+            std::process::exit(1);
+        })
+    }
+
+    /// The rules of a guild: its own ones, or these.
+    pub fn of_guild(&self, guild_id: GuildId, guild_settings: &GuildSettings) -> Self {
+        match &guild_settings.title_rules {
+            Some(rules) => Self::compile(rules).unwrap_or_else(|e| {
+                warn!("invalid title rules in guild {}: {}", guild_id, e);
+                self.clone()
+            }),
+            None => self.clone(),
+        }
+    }
+
+    /// Run a title through every rule. Rules finding the artist are skipped
+    /// when `has_artist`.
+    pub fn clean(&self, original: &str, has_artist: bool) -> CleanTitle {
+        let mut title = original.to_string();
+        let mut artist = None;
+
+        for (regex, rule) in &self.0 {
+            match &rule.artist {
+                Some(_) if has_artist || artist.is_some() => continue,
+                Some(artist_template) => {
+                    let captures = match regex.captures(&title) {
+                        Some(captures) => captures,
+                        None => continue,
+                    };
+                    let mut found_artist = String::new();
+                    captures.expand(artist_template, &mut found_artist);
+                    let mut new_title = String::new();
+                    captures.expand(&rule.replacement, &mut new_title);
+                    // a rule leaving nothing is more likely wrong than right
+                    if found_artist.trim().is_empty() || new_title.trim().is_empty() {
+                        continue;
+                    }
+                    artist = Some(found_artist.trim().to_string());
+                    title = regex.replace(&title, rule.replacement.as_str()).to_string();
+                }
+                None => {
+                    title = regex
+                        .replace_all(&title, rule.replacement.as_str())
+                        .to_string();
+                }
+            }
+            title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+
+        // rules can't leave a track without a title
+        if title.is_empty() {
+            title = original.trim().to_string();
+        }
+        CleanTitle { title, artist }
+    }
+
+    /// The rules, to show or change them.
+    pub fn rules(&self) -> Vec<TitleRule> {
+        self.0.iter().map(|(_, rule)| rule.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(title: &str, has_artist: bool) -> (String, Option<String>) {
+        let title_rules = TitleRules::compile(&default_rules()).unwrap();
+        let clean_title = title_rules.clean(title, has_artist);
+        (clean_title.title, clean_title.artist)
+    }
+
+    #[test]
+    fn removes_tags() {
+        assert_eq!(
+            clean("Song (Official Music Video)", true),
+            ("Song".to_string(), None)
+        );
+        assert_eq!(
+            clean("【MV】Song【公式】", true),
+            ("Song".to_string(), None)
+        );
+        assert_eq!(
+            clean("Song | Official Lyrics Video", true),
+            ("Song".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn finds_artists() {
+        assert_eq!(
+            clean("Artist - Song [Official Video]", false),
+            ("Song".to_string(), Some("Artist".to_string()))
+        );
+        assert_eq!(
+            clean("米津玄師 MV「Lemon」", false),
+            ("Lemon".to_string(), Some("米津玄師".to_string()))
+        );
+    }
+
+    #[test]
+    fn keeps_known_artists() {
+        assert_eq!(
+            clean("Artist - Song", true),
+            ("Artist - Song".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn never_leaves_titles_empty() {
+        assert_eq!(clean("(Official Video)", true).0, "(Official Video)");
+    }
+
+    #[test]
+    fn rejects_invalid_regexes() {
+        let rules = [TitleRule::new("(", "", None)];
+        assert!(TitleRules::compile(&rules).is_err());
+    }
+}
@@ -7,7 +7,7 @@ use data::{config::Config, Data};
 
 use dotenvy::dotenv;
 use poise::{
-    serenity_prelude::{ClientBuilder, GatewayIntents},
+    serenity_prelude::{ClientBuilder, FullEvent, GatewayIntents, Interaction},
     FrameworkError, FrameworkOptions,
 };
 use songbird::SerenityInit;
@@ -32,6 +32,29 @@ impl Display for AppError {
 
 type Context<'a> = poise::Context<'a, Data, AppError>;
 
+/// Wait for Ctrl+C, or SIGTERM from `docker stop` and such.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => error!("can't listen for SIGTERM: {}", e),
+        }
+    }
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        error!("can't listen for Ctrl+C: {}", e);
+        std::future::pending::<()>().await;
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     if dotenv().is_err() {
@@ -54,17 +77,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             },
             commands: vec![
                 commands::ping::ping(),
+                commands::diagnostics::diagnostics(),
                 commands::help::help(),
+                commands::settings::settings(),
+                commands::admin::admin(),
+                commands::blocklist::blocklist(),
                 commands::qt::qt(),
                 commands::qt::qt_cm(),
                 commands::kqt::kqt(),
                 commands::kqt::kqt_cm(),
                 commands::dcl::dcl(),
+                commands::player::join(),
+                commands::player::summon(),
+                commands::player::leave(),
                 commands::player::play(),
                 commands::player::pause(),
                 commands::player::queue(),
+                commands::player::history(),
+                commands::player::stats(),
+                commands::player::radio(),
+                commands::player::stations(),
+                commands::player::sfx(),
+                commands::player::sleep(),
+                commands::player::schedule(),
                 commands::player::restart(),
                 commands::player::skip(),
+                commands::player::cancel(),
                 commands::player::nuke(),
             ],
             on_error: |error: FrameworkError<Data, AppError>| {
@@ -81,6 +119,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             error!("ArgumentParse error: {}", error);
                         }
 
+                        // a check refused to run the command, tell why
+                        poise::FrameworkError::CommandCheckFailed { error, ctx, .. } => {
+                            if let Some(error) = error {
+                                let _ = ctx.say(error.to_string()).await;
+                            }
+                        }
+
                         // error in commands
                         poise::FrameworkError::Command { error, ctx, .. } => {
                             error!("Command error: {}", error);
@@ -113,12 +158,42 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     }
                 })
             },
+            event_handler: |ctx, event, _framework, data| {
+                Box::pin(async move {
+                    // buttons of the "now playing" panels
+                    if let FullEvent::InteractionCreate {
+                        interaction: Interaction::Component(interaction),
+                    } = event
+                    {
+                        commands::player::handle_panel_press(ctx, data, interaction).await;
+                    }
+                    if let FullEvent::VoiceStateUpdate { old, new } = event {
+                        commands::player::handle_voice_state_update(ctx, data, old.as_ref(), new)
+                            .await;
+                    }
+                    Ok(())
+                })
+            },
             ..Default::default()
         })
         .setup(move |ctx, _ready, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Data::new(config, framework.shard_manager().clone()))
+                let data = Data::new(config, framework.shard_manager().clone()).await;
+                commands::player::start_scheduler(ctx, &data);
+                commands::player::start_breakage_reports(ctx, &data);
+                data.stats.start_flushing();
+
+                // save what's only in memory before going down
+                let stats = data.stats.clone();
+                let shard_manager = data.shard_manager.clone();
+                tokio::spawn(async move {
+                    shutdown_signal().await;
+                    info!("shutting down");
+                    stats.flush().await;
+                    shard_manager.shutdown_all().await;
+                });
+                Ok(data)
             })
         })
         .build();